@@ -0,0 +1,100 @@
+//! Lining nodes up, by snapping a moved node or by arranging a selection.
+
+/// How close two edges have to be for a moved node to snap to the other
+/// node, the editor draws its guides within the same distance.
+const SNAP_DISTANCE: f32 = 5.;
+
+/// `(x, y, width, height)` in graph coordinates.
+type Rect = (f32, f32, f32, f32);
+
+/// Which line of the selected nodes to bring together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    Top,
+    /// Centres on a common vertical line.
+    Center,
+    /// Centres on a common horizontal line.
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// Where a node moved to `rect` should end up: its left edge or centre
+/// snaps to one of `others` when close enough, and to the grid otherwise.
+/// Returns the top-left corner.
+pub fn snap(rect: Rect, others: &[Rect], grid: Option<f32>) -> (f32, f32) {
+    let (x, y, w, h) = rect;
+    let to_grid = |v: f32| grid.map_or(v, |g| (v / g).round() * g);
+    let x = nearest(x, others.iter().map(|o| o.0))
+        .or_else(|| nearest(x + w / 2., others.iter().map(|o| o.0 + o.2 / 2.)).map(|c| c - w / 2.))
+        .unwrap_or_else(|| to_grid(x));
+    let y = nearest(y, others.iter().map(|o| o.1))
+        .or_else(|| nearest(y + h / 2., others.iter().map(|o| o.1 + o.3 / 2.)).map(|c| c - h / 2.))
+        .unwrap_or_else(|| to_grid(y));
+    (x, y)
+}
+
+/// The closest of `candidates` within snapping distance of `v`.
+fn nearest(v: f32, candidates: impl Iterator<Item = f32>) -> Option<f32> {
+    candidates
+        .filter(|c| (c - v).abs() < SNAP_DISTANCE)
+        .min_by(|a, b| (a - v).abs().total_cmp(&(b - v).abs()))
+}
+
+/// New top-left corners lining `nodes` up on the outermost left or top
+/// edge, or on the centre of their bounding box.
+pub fn align(nodes: &[(usize, Rect)], alignment: Alignment) -> Vec<(usize, (f32, f32))> {
+    let min = |f: fn(&Rect) -> f32| nodes.iter().map(|(_, r)| f(r)).fold(f32::MAX, f32::min);
+    let max = |f: fn(&Rect) -> f32| nodes.iter().map(|(_, r)| f(r)).fold(f32::MIN, f32::max);
+    let center = (min(|r| r.0) + max(|r| r.0 + r.2)) / 2.;
+    let middle = (min(|r| r.1) + max(|r| r.1 + r.3)) / 2.;
+    nodes
+        .iter()
+        .map(|(idx, (x, y, w, h))| {
+            let pos = match alignment {
+                Alignment::Left => (min(|r| r.0), *y),
+                Alignment::Top => (*x, min(|r| r.1)),
+                Alignment::Center => (center - w / 2., *y),
+                Alignment::Middle => (*x, middle - h / 2.),
+            };
+            (*idx, pos)
+        })
+        .collect()
+}
+
+/// New top-left corners leaving the same gap between neighbouring `nodes`
+/// along `axis`, the outermost ones stay in place.
+pub fn distribute(nodes: &[(usize, Rect)], axis: Axis) -> Vec<(usize, (f32, f32))> {
+    // position and extent along the axis
+    let span = |r: &Rect| match axis {
+        Axis::Horizontal => (r.0, r.2),
+        Axis::Vertical => (r.1, r.3),
+    };
+    let mut sorted = nodes.to_vec();
+    sorted.sort_by(|a, b| span(&a.1).0.total_cmp(&span(&b.1).0));
+    let (Some(first), Some(last)) = (sorted.first(), sorted.last()) else {
+        return vec![];
+    };
+    let start = span(&first.1).0;
+    let end = span(&last.1).0 + span(&last.1).1;
+    let total = sorted.iter().map(|(_, r)| span(r).1).sum::<f32>();
+    let gap = (end - start - total) / (sorted.len() - 1).max(1) as f32;
+
+    let mut at = start;
+    sorted
+        .iter()
+        .map(|(idx, r)| {
+            let pos = match axis {
+                Axis::Horizontal => (at, r.1),
+                Axis::Vertical => (r.0, at),
+            };
+            at += span(r).1 + gap;
+            (*idx, pos)
+        })
+        .collect()
+}
@@ -0,0 +1,408 @@
+use crate::{catalog::Widget, Catalog, LinkType, NodeType};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tungstenite::Message;
+
+const DEFAULT_URL: &str = "http://127.0.0.1:8188";
+/// How often the history is checked while waiting for a prompt.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Types of the inputs set by a value rather than a link.
+const WIDGET_TYPES: &[&str] = &["INT", "FLOAT", "STRING", "BOOLEAN", "COMBO"];
+/// Integer inputs the frontend gives a "control after generate" widget
+/// even when the backend doesn't ask for one.
+const SEED_INPUTS: &[&str] = &["seed", "noise_seed"];
+
+#[derive(Debug, Clone)]
+pub struct Backend {
+    url: String,
+    /// Tells the backend which WebSocket to report the progress of our
+    /// prompts on.
+    client_id: String,
+}
+impl Default for Backend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend {
+    pub fn new() -> Self {
+        Self::with_url(DEFAULT_URL)
+    }
+    pub fn with_url(url: &str) -> Self {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        Self {
+            url: url.trim_end_matches('/').into(),
+            client_id: format!("kira-{}-{}", std::process::id(), millis),
+        }
+    }
+}
+
+impl Backend {
+    /// The nodes the backend offers, keeping only the inputs fed by links.
+    pub fn available_nodes(&self) -> Result<Catalog, Box<dyn Error>> {
+        let url = format!("{}/object_info", self.url);
+        let nodes = reqwest::blocking::get(url)?.error_for_status()?.json()?;
+        Ok(into_catalog(nodes))
+    }
+    /// Queues a prompt for execution and returns its id.
+    pub fn queue_prompt(&self, prompt: &Value) -> Result<String, Box<dyn Error>> {
+        let url = format!("{}/prompt", self.url);
+
+        let response = reqwest::blocking::Client::new()
+            .post(url)
+            .json(&json!({ "prompt": prompt, "client_id": self.client_id }))
+            .send()?;
+
+        if !response.status().is_success() {
+            return Err(response.text()?.into());
+        }
+
+        let queued: QueuedPrompt = response.json()?;
+        Ok(queued.prompt_id)
+    }
+    /// Takes prompts `ids` off the queue, interrupting the one running if
+    /// it is among them.
+    pub fn cancel(&self, ids: &[String]) -> Result<(), Box<dyn Error>> {
+        let client = reqwest::blocking::Client::new();
+        client
+            .post(format!("{}/queue", self.url))
+            .json(&json!({ "delete": ids }))
+            .send()?
+            .error_for_status()?;
+        let running = self
+            .queue()?
+            .running()
+            .any(|id| ids.iter().any(|i| i == id));
+        if running {
+            client
+                .post(format!("{}/interrupt", self.url))
+                .send()?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+    /// Queues a prompt and blocks until the backend reports it has run,
+    /// following its progress over the WebSocket. Returns its id.
+    pub fn run_prompt(&self, prompt: &Value) -> Result<String, Box<dyn Error>> {
+        let url = format!(
+            "{}/ws?clientId={}",
+            self.url.replacen("http", "ws", 1),
+            self.client_id
+        );
+        // connected first so the end of a quick prompt isn't missed
+        let (mut socket, _) = tungstenite::connect(url)?;
+        let id = self.queue_prompt(prompt)?;
+        loop {
+            let Message::Text(text) = socket.read()? else {
+                continue;
+            };
+            let Ok(status) = serde_json::from_str::<StatusMessage>(&text) else {
+                continue;
+            };
+            if status.data.get("prompt_id").and_then(Value::as_str) != Some(id.as_str()) {
+                continue;
+            }
+            match status.ty.as_str() {
+                // executing nothing means the prompt is done
+                "executing" if status.data.get("node").is_some_and(Value::is_null) => {
+                    return Ok(id)
+                }
+                "execution_success" => return Ok(id),
+                "execution_error" => {
+                    let message = status
+                        .data
+                        .get("exception_message")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown error");
+                    return Err(format!("prompt {} failed: {}", id, message).into());
+                }
+                "execution_interrupted" => {
+                    return Err(format!("prompt {} was interrupted", id).into())
+                }
+                _ => {}
+            }
+        }
+    }
+    /// Blocks until prompt `id` has run, then downloads the images it
+    /// produced into `dir`. Returns their paths by prompt node id. Fails if
+    /// the prompt leaves the queue without running, like when it is
+    /// cancelled or the backend restarts.
+    pub fn wait_for_outputs(
+        &self,
+        id: &str,
+        dir: &Path,
+    ) -> Result<HashMap<String, Vec<PathBuf>>, Box<dyn Error>> {
+        let entry = loop {
+            // the prompt only shows up in the history once it has run
+            if let Some(entry) = self.history_entry(id)? {
+                break entry;
+            }
+            if !self.queue()?.contains(id) {
+                // it may have finished since the history was checked
+                match self.history_entry(id)? {
+                    Some(entry) => break entry,
+                    None => return Err(format!("prompt {} is no longer queued", id).into()),
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        if entry.status.status_str == "error" {
+            return Err(format!("prompt {} failed", id).into());
+        }
+
+        fs::create_dir_all(dir)?;
+        let mut outputs = HashMap::new();
+        for (node, output) in entry.outputs {
+            let paths = output
+                .images
+                .iter()
+                .map(|image| self.download(image, dir))
+                .collect::<Result<Vec<_>, _>>()?;
+            if !paths.is_empty() {
+                outputs.insert(node, paths);
+            }
+        }
+        Ok(outputs)
+    }
+    fn history_entry(&self, id: &str) -> Result<Option<HistoryEntry>, Box<dyn Error>> {
+        let url = format!("{}/history/{}", self.url, id);
+        let mut history: HashMap<String, HistoryEntry> =
+            reqwest::blocking::get(url)?.error_for_status()?.json()?;
+        Ok(history.remove(id))
+    }
+    fn queue(&self) -> Result<Queue, Box<dyn Error>> {
+        let url = format!("{}/queue", self.url);
+        Ok(reqwest::blocking::get(url)?.error_for_status()?.json()?)
+    }
+    fn download(&self, image: &OutputImage, dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        let url = format!("{}/view", self.url);
+        let bytes = reqwest::blocking::Client::new()
+            .get(url)
+            .query(&[
+                ("filename", &image.filename),
+                ("subfolder", &image.subfolder),
+                ("type", &image.kind),
+            ])
+            .send()?
+            .error_for_status()?
+            .bytes()?;
+        // only the file name, the backend's folders mean nothing here
+        let name = Path::new(&image.filename)
+            .file_name()
+            .ok_or_else(|| format!("invalid image name {}", image.filename))?;
+        let path = dir.join(name);
+        fs::write(&path, bytes)?;
+        Ok(path)
+    }
+}
+
+/// A progress report sent over the WebSocket.
+#[derive(Deserialize, Debug)]
+struct StatusMessage {
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default)]
+    data: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct QueuedPrompt {
+    prompt_id: String,
+}
+
+/// Entries are `[number, prompt_id, prompt, extra_data, outputs]`.
+#[derive(Deserialize, Debug)]
+struct Queue {
+    #[serde(default)]
+    queue_running: Vec<Vec<Value>>,
+    #[serde(default)]
+    queue_pending: Vec<Vec<Value>>,
+}
+
+impl Queue {
+    fn running(&self) -> impl Iterator<Item = &str> {
+        self.queue_running
+            .iter()
+            .filter_map(|entry| entry.get(1)?.as_str())
+    }
+    /// Whether prompt `id` is running or waiting to.
+    fn contains(&self, id: &str) -> bool {
+        self.queue_running
+            .iter()
+            .chain(&self.queue_pending)
+            .any(|entry| entry.get(1).and_then(Value::as_str) == Some(id))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct HistoryEntry {
+    #[serde(default)]
+    outputs: HashMap<String, NodeOutput>,
+    #[serde(default)]
+    status: PromptStatus,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct PromptStatus {
+    #[serde(default)]
+    status_str: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct NodeOutput {
+    #[serde(default)]
+    images: Vec<OutputImage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OutputImage {
+    filename: String,
+    #[serde(default)]
+    subfolder: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// The catalog of an `object_info` response, by node type name.
+pub(crate) fn into_catalog(nodes: HashMap<String, Node>) -> Catalog {
+    nodes
+        .into_iter()
+        .map(|(ty, node)| (NodeType(ty), node.into_node()))
+        .collect()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Node {
+    #[serde(default)]
+    pub input: NodeInputs,
+    /// Missing from older backends.
+    #[serde(default)]
+    pub input_order: InputOrder,
+    pub output: Vec<String>,
+    pub output_is_list: Vec<bool>,
+    pub output_name: Vec<String>,
+    pub name: String,
+    pub display_name: String,
+    pub description: String,
+    pub category: String,
+    pub output_node: bool,
+    #[serde(default)]
+    pub python_module: String,
+}
+
+/// Specs of the inputs by name, like `["INT", {"min": 0}]`, or a list of
+/// choices for a combo box.
+#[derive(Deserialize, Debug, Default)]
+pub struct NodeInputs {
+    #[serde(default)]
+    pub required: Map<String, Value>,
+    #[serde(default)]
+    pub optional: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct InputOrder {
+    #[serde(default)]
+    pub required: Vec<String>,
+    #[serde(default)]
+    pub optional: Vec<String>,
+}
+
+impl Node {
+    /// The node as offered in the editor, with a slot for each input fed by
+    /// a link and a widget for each input set by a value.
+    fn into_node(self) -> crate::Node {
+        let mut inputs = vec![];
+        let mut widgets = vec![];
+        for (name, ty, spec) in self.ordered_inputs() {
+            let options = spec.get(1).unwrap_or(&Value::Null);
+            let forced = options.get("forceInput").and_then(Value::as_bool) == Some(true);
+            if !WIDGET_TYPES.contains(&ty) || forced {
+                inputs.push((name.to_owned(), LinkType(ty.to_owned())));
+            } else {
+                widgets.push(widget(name, ty, spec));
+            }
+        }
+        crate::Node {
+            inputs,
+            widgets,
+            outputs: self
+                .output_name
+                .into_iter()
+                .zip(self.output)
+                .map(|(name, ty)| (name, LinkType(ty)))
+                .collect(),
+            name: self.display_name,
+            description: self.description,
+            category: self.category,
+            pack: self.python_module,
+        }
+    }
+    /// Names, types and specs of the inputs, required ones first. Without
+    /// an order from the backend they come sorted by name.
+    fn ordered_inputs(&self) -> Vec<(&str, &str, &Value)> {
+        let groups = [
+            (&self.input_order.required, &self.input.required),
+            (&self.input_order.optional, &self.input.optional),
+        ];
+        let mut inputs = vec![];
+        for (order, specs) in groups {
+            let names = if order.is_empty() {
+                specs.keys().collect::<Vec<_>>()
+            } else {
+                order.iter().collect()
+            };
+            for name in names {
+                let Some(spec) = specs.get(name) else {
+                    continue;
+                };
+                let ty = match spec.get(0) {
+                    Some(Value::String(ty)) => ty.as_str(),
+                    Some(Value::Array(_)) => "COMBO",
+                    _ => continue,
+                };
+                inputs.push((name.as_str(), ty, spec));
+            }
+        }
+        inputs
+    }
+}
+
+/// The widget the frontend shows for input `name` of type `ty`. Without a
+/// default in `spec` it starts at zero, empty or the first choice.
+fn widget(name: &str, ty: &str, spec: &Value) -> Widget {
+    let options = spec.get(1).unwrap_or(&Value::Null);
+    let choices = match spec.get(0) {
+        Some(Value::Array(choices)) => Some(choices),
+        _ => options.get("options").and_then(Value::as_array),
+    };
+    let default = match options.get("default") {
+        Some(default) => default.clone(),
+        None => match ty {
+            "INT" | "FLOAT" => json!(0),
+            "STRING" => json!(""),
+            "BOOLEAN" => json!(false),
+            _ => choices.and_then(|c| c.first()).cloned().unwrap_or_default(),
+        },
+    };
+    let flag = |key| options.get(key).and_then(Value::as_bool) == Some(true);
+    let control = flag("control_after_generate") || (ty == "INT" && SEED_INPUTS.contains(&name));
+    Widget {
+        name: name.into(),
+        default,
+        extra_values: control as usize + flag("image_upload") as usize,
+    }
+}
@@ -0,0 +1,137 @@
+//! The node types a graph can be built from.
+
+use crate::graph::{Graph, Link, LinkType, NodeType, Port, Subgraph};
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashMap};
+
+/// Node types by name, offered by the backend, the editor itself and the
+/// subgraphs of a project.
+pub type Catalog = HashMap<NodeType, Node>;
+
+/// Replaces the subgraph node types of `catalog` by those `root` defines.
+pub fn set_subgraphs(catalog: &mut Catalog, root: &Graph) {
+    catalog.retain(|ty, _| ty.subgraph_name().is_none());
+    for (name, subgraph) in root.subgraphs() {
+        catalog.insert(NodeType::subgraph(name), subgraph_node(name, subgraph));
+    }
+}
+
+fn subgraph_node(name: &str, subgraph: &Subgraph) -> Node {
+    let slots = |ports: &[Port]| {
+        ports
+            .iter()
+            .map(|p| (p.name.clone(), p.ty.clone()))
+            .collect()
+    };
+    Node {
+        inputs: slots(&subgraph.inputs),
+        widgets: vec![],
+        outputs: slots(&subgraph.outputs),
+        name: name.into(),
+        description: format!("Subgraph of {} nodes", subgraph.graph.get_nodes().len()),
+        category: "subgraphs".into(),
+        pack: "project".into(),
+    }
+}
+
+/// The node type of node `idx` of `graph`, or a placeholder when `catalog`
+/// doesn't have it.
+pub fn node_of<'a>(catalog: &'a Catalog, graph: &Graph, idx: usize) -> Option<Cow<'a, Node>> {
+    let ty = &graph.get_node(idx)?.ty;
+    Some(match catalog.get(ty) {
+        Some(node) => Cow::Borrowed(node),
+        None => Cow::Owned(placeholder(ty, idx, graph.get_links())),
+    })
+}
+
+/// Stands in for a node type the catalog doesn't have, like a custom node
+/// of a workflow, with the slots the links of node `idx` use.
+pub fn placeholder(ty: &NodeType, idx: usize, links: &[Link]) -> Node {
+    let mut inputs = vec![];
+    let mut outputs = vec![];
+    for link in links {
+        if link.dst_node == idx {
+            set_slot(&mut inputs, link.dst_slot, &link.ty);
+        }
+        if link.src_node == idx {
+            set_slot(&mut outputs, link.src_slot, &link.ty);
+        }
+    }
+    Node {
+        inputs,
+        widgets: vec![],
+        outputs,
+        name: ty.0.clone(),
+        description: "Not offered by the backend".into(),
+        category: "missing".into(),
+        pack: String::new(),
+    }
+}
+
+fn set_slot(slots: &mut Vec<(String, LinkType)>, slot: usize, ty: &LinkType) {
+    if slots.len() <= slot {
+        slots.resize_with(slot + 1, || (String::new(), LinkType::any()));
+    }
+    slots[slot] = (ty.0.to_lowercase(), ty.clone());
+}
+
+/// Nodes handled by the editor itself rather than the backend.
+pub fn builtin_nodes() -> Catalog {
+    HashMap::from([
+        (
+            NodeType::note(),
+            Node {
+                inputs: vec![],
+                widgets: vec![],
+                outputs: vec![],
+                name: "Note".into(),
+                description: "Free text shown on the canvas, supports **bold** and *italic*".into(),
+                category: "utils".into(),
+                pack: "builtin".into(),
+            },
+        ),
+        (
+            NodeType::reroute(),
+            Node {
+                inputs: vec![("".into(), LinkType::any())],
+                widgets: vec![],
+                outputs: vec![("".into(), LinkType::any())],
+                name: "Reroute".into(),
+                description: "Pass-through point to tidy up long links".into(),
+                category: "utils".into(),
+                pack: "builtin".into(),
+            },
+        ),
+    ])
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub inputs: Vec<(String, LinkType)>,
+    pub widgets: Vec<Widget>,
+    pub outputs: Vec<(String, LinkType)>,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    pub pack: String,
+}
+
+impl Node {
+    pub fn search_string(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.name, self.description, self.category, self.pack
+        )
+    }
+}
+
+/// An input set by a value rather than a link.
+#[derive(Debug, Clone)]
+pub struct Widget {
+    pub name: String,
+    /// Value the frontend starts the widget at.
+    pub default: Value,
+    /// How many values workflows store after this one that aren't inputs,
+    /// like the "control after generate" choice of seeds.
+    pub extra_values: usize,
+}
@@ -0,0 +1,405 @@
+//! Writing the timeline out as images or for an external encoder.
+
+use crate::Timeline;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::{self, FilterType},
+    Delay, Frame, ImageFormat, Rgba, RgbaImage,
+};
+use libwebp_sys::{WebPAnimEncoder, WebPConfig, WebPData, WebPPicture};
+use std::{
+    error::Error,
+    ffi::CStr,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter},
+    mem::MaybeUninit,
+    path::{Path, PathBuf},
+    ptr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Numbered PNG files next to the chosen path.
+    Frames,
+    Gif,
+    Apng,
+    WebP,
+    /// A frame list for ffmpeg's concat demuxer and a script encoding it.
+    Ffmpeg,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "frames" => Some(ExportFormat::Frames),
+            "gif" => Some(ExportFormat::Gif),
+            "apng" => Some(ExportFormat::Apng),
+            "webp" => Some(ExportFormat::WebP),
+            "ffmpeg" => Some(ExportFormat::Ffmpeg),
+            _ => None,
+        }
+    }
+    /// Filter name and extensions of the save dialog.
+    pub fn filter(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            ExportFormat::Frames => ("PNG Frames", &["png"]),
+            ExportFormat::Gif => ("Animated GIF", &["gif"]),
+            ExportFormat::Apng => ("Animated PNG", &["png", "apng"]),
+            ExportFormat::WebP => ("Animated WebP", &["webp"]),
+            ExportFormat::Ffmpeg => ("Shell Script", &["sh"]),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub fps: f32,
+    /// Width and height of the frames, those of the first image if `None`.
+    pub size: Option<(u32, u32)>,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Empty,
+    Io(io::Error),
+    Image(image::ImageError),
+    Png(png::EncodingError),
+    WebP(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Empty => write!(f, "the timeline has no images"),
+            ExportError::Io(e) => write!(f, "{}", e),
+            ExportError::Image(e) => write!(f, "{}", e),
+            ExportError::Png(e) => write!(f, "{}", e),
+            ExportError::WebP(e) => write!(f, "could not encode WebP: {}", e),
+        }
+    }
+}
+
+impl Error for ExportError {}
+
+impl From<io::Error> for ExportError {
+    fn from(value: io::Error) -> Self {
+        ExportError::Io(value)
+    }
+}
+impl From<image::ImageError> for ExportError {
+    fn from(value: image::ImageError) -> Self {
+        ExportError::Image(value)
+    }
+}
+impl From<png::EncodingError> for ExportError {
+    fn from(value: png::EncodingError) -> Self {
+        ExportError::Png(value)
+    }
+}
+
+/// Writes the whole timeline to `path`, resampled at the export frame rate.
+/// Gaps between segments come out black.
+pub fn export_timeline(
+    timeline: &Timeline,
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), ExportError> {
+    let path = Path::new(path);
+    let fps = options.fps.max(1.);
+    let count = (timeline.end() * fps).ceil() as usize;
+    let paths = (0..count)
+        .map(|frame| timeline.image_at_time(frame as f32 / fps))
+        .collect::<Vec<_>>();
+    let size = match options.size {
+        Some(size) => size,
+        None => {
+            let first = paths.iter().flatten().next().ok_or(ExportError::Empty)?;
+            image::image_dimensions(first)?
+        }
+    };
+    if paths.is_empty() {
+        return Err(ExportError::Empty);
+    }
+    let mut frames = Frames::new(&paths, size);
+    match options.format {
+        ExportFormat::Frames => {
+            let mut n = 0;
+            while let Some(frame) = frames.next_frame() {
+                n += 1;
+                frame?.save_with_format(numbered(path, n), ImageFormat::Png)?;
+            }
+        }
+        ExportFormat::Gif => write_gif(path, frames, fps)?,
+        ExportFormat::Apng => write_apng(path, frames, size, fps)?,
+        ExportFormat::WebP => write_webp(path, frames, size, fps)?,
+        ExportFormat::Ffmpeg => write_ffmpeg(path, &paths, options.size, size, fps)?,
+    }
+    Ok(())
+}
+
+/// Loads the image of each frame at `size` when it's encoded, so only one
+/// is in memory at a time. Frames tend to show the same image as the one
+/// before, which is then reused rather than read again.
+struct Frames<'a> {
+    paths: std::slice::Iter<'a, Option<&'a str>>,
+    size: (u32, u32),
+    last: Option<(Option<&'a str>, RgbaImage)>,
+}
+
+impl<'a> Frames<'a> {
+    fn new(paths: &'a [Option<&'a str>], size: (u32, u32)) -> Self {
+        Frames {
+            paths: paths.iter(),
+            size,
+            last: None,
+        }
+    }
+    /// How many frames are left.
+    fn len(&self) -> usize {
+        self.paths.len()
+    }
+    fn next_frame(&mut self) -> Option<Result<&RgbaImage, ExportError>> {
+        let path = *self.paths.next()?;
+        if !matches!(&self.last, Some((last, _)) if *last == path) {
+            let (width, height) = self.size;
+            let image = match path {
+                Some(path) => match image::open(path) {
+                    Ok(image) => {
+                        let image = image.into_rgba8();
+                        if image.dimensions() == self.size {
+                            image
+                        } else {
+                            imageops::resize(&image, width, height, FilterType::Triangle)
+                        }
+                    }
+                    Err(e) => return Some(Err(e.into())),
+                },
+                None => RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255])),
+            };
+            self.last = Some((path, image));
+        }
+        self.last.as_ref().map(|(_, image)| Ok(image))
+    }
+}
+
+/// `dir/name_00001.png` for `dir/name.png`.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{:05}.png", stem, n))
+}
+
+fn write_gif(path: &Path, mut frames: Frames, fps: f32) -> Result<(), ExportError> {
+    let delay = Delay::from_numer_denom_ms(100_000, (fps * 100.).round() as u32);
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    while let Some(frame) = frames.next_frame() {
+        encoder.encode_frame(Frame::from_parts(frame?.clone(), 0, 0, delay))?;
+    }
+    Ok(())
+}
+
+fn write_apng(
+    path: &Path,
+    mut frames: Frames,
+    (width, height): (u32, u32),
+    fps: f32,
+) -> Result<(), ExportError> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // loops forever
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(100, (fps * 100.).round() as u16)?;
+    let mut writer = encoder.write_header()?;
+    while let Some(frame) = frames.next_frame() {
+        writer.write_image_data(frame?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+fn write_webp(
+    path: &Path,
+    mut frames: Frames,
+    size: (u32, u32),
+    fps: f32,
+) -> Result<(), ExportError> {
+    let timestamp = |i: usize| (i as f32 * 1000. / fps).round() as i32;
+    let mut encoder = WebPWriter::new(size)?;
+    let mut count = 0;
+    while let Some(frame) = frames.next_frame() {
+        encoder.add(frame?, timestamp(count))?;
+        count += 1;
+    }
+    fs::write(path, encoder.finish(timestamp(count))?)?;
+    Ok(())
+}
+
+/// A lossless animated WebP encoder taking one frame at a time. The `webp`
+/// crate's holds on to every frame until the end, libwebp itself only keeps
+/// what it needs to encode the next one.
+struct WebPWriter {
+    encoder: *mut WebPAnimEncoder,
+    config: WebPConfig,
+    size: (u32, u32),
+}
+
+impl WebPWriter {
+    fn new(size: (u32, u32)) -> Result<Self, ExportError> {
+        let invalid = |_| ExportError::WebP("invalid configuration".into());
+        let mut config = WebPConfig::new().map_err(invalid)?;
+        config.lossless = 1;
+        let version = libwebp_sys::WebPGetMuxABIVersion();
+        // SAFETY: the options are initialised by libwebp before being read
+        let encoder = unsafe {
+            let mut options = MaybeUninit::uninit();
+            if libwebp_sys::WebPAnimEncoderOptionsInitInternal(options.as_mut_ptr(), version) == 0 {
+                return Err(invalid(()));
+            }
+            libwebp_sys::WebPAnimEncoderNewInternal(
+                size.0 as i32,
+                size.1 as i32,
+                options.as_ptr(),
+                version,
+            )
+        };
+        if encoder.is_null() {
+            return Err(invalid(()));
+        }
+        Ok(WebPWriter {
+            encoder,
+            config,
+            size,
+        })
+    }
+    /// Adds `frame`, shown from `timestamp` in milliseconds on.
+    fn add(&mut self, frame: &RgbaImage, timestamp: i32) -> Result<(), ExportError> {
+        let (width, height) = self.size;
+        let mut picture =
+            WebPPicture::new().map_err(|_| ExportError::WebP("invalid picture".into()))?;
+        picture.use_argb = 1;
+        picture.width = width as i32;
+        picture.height = height as i32;
+        // SAFETY: the frame has `size` and libwebp copies it into the picture,
+        // which it's done with once the frame is added
+        unsafe {
+            let imported =
+                libwebp_sys::WebPPictureImportRGBA(&mut picture, frame.as_ptr(), width as i32 * 4);
+            let added = imported != 0
+                && libwebp_sys::WebPAnimEncoderAdd(
+                    self.encoder,
+                    &mut picture,
+                    timestamp,
+                    &self.config,
+                ) != 0;
+            libwebp_sys::WebPPictureFree(&mut picture);
+            if !added {
+                return Err(self.error());
+            }
+        }
+        Ok(())
+    }
+    /// Ends the last frame at `timestamp` and returns the file.
+    fn finish(self, timestamp: i32) -> Result<Vec<u8>, ExportError> {
+        // SAFETY: the data is owned by us once assembled and freed after the
+        // copy
+        unsafe {
+            let mut data = WebPData::default();
+            let assembled = libwebp_sys::WebPAnimEncoderAdd(
+                self.encoder,
+                ptr::null_mut(),
+                timestamp,
+                ptr::null(),
+            ) != 0
+                && libwebp_sys::WebPAnimEncoderAssemble(self.encoder, &mut data) != 0;
+            if !assembled {
+                return Err(self.error());
+            }
+            let bytes = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+            libwebp_sys::WebPDataClear(&mut data);
+            Ok(bytes)
+        }
+    }
+    fn error(&self) -> ExportError {
+        // SAFETY: the message lives as long as the encoder
+        let message = unsafe { libwebp_sys::WebPAnimEncoderGetError(self.encoder) };
+        if message.is_null() {
+            return ExportError::WebP("unknown error".into());
+        }
+        let message = unsafe { CStr::from_ptr(message) };
+        ExportError::WebP(message.to_string_lossy().into_owned())
+    }
+}
+
+impl Drop for WebPWriter {
+    fn drop(&mut self) {
+        // SAFETY: the encoder was created in `new` and isn't used after this
+        unsafe { libwebp_sys::WebPAnimEncoderDelete(self.encoder) };
+    }
+}
+
+/// Writes the frame list next to the script at `path`, one entry per run
+/// of frames showing the same image. Gaps show a black image written along
+/// with them. The images are only scaled when a size was asked for.
+fn write_ffmpeg(
+    path: &Path,
+    paths: &[Option<&str>],
+    scale: Option<(u32, u32)>,
+    size: (u32, u32),
+    fps: f32,
+) -> Result<(), ExportError> {
+    let list = path.with_extension("txt");
+    let output = path.with_extension("mp4");
+    let blank = path.with_extension("blank.png");
+    if paths.iter().any(Option::is_none) {
+        RgbaImage::from_pixel(size.0, size.1, Rgba([0, 0, 0, 255]))
+            .save_with_format(&blank, ImageFormat::Png)?;
+    }
+    let blank = blank.to_string_lossy().into_owned();
+
+    let mut runs: Vec<(&str, usize)> = vec![];
+    for path in paths {
+        let path = path.unwrap_or(&blank);
+        match runs.last_mut() {
+            Some((last, count)) if *last == path => *count += 1,
+            _ => runs.push((path, 1)),
+        }
+    }
+    if runs.is_empty() {
+        return Err(ExportError::Empty);
+    }
+    let mut entries = String::from("ffconcat version 1.0\n");
+    for (path, count) in &runs {
+        entries += &format!("file {}\nduration {}\n", quote(path), *count as f32 / fps);
+    }
+    // the demuxer ignores the duration of the last entry unless it's repeated
+    entries += &format!("file {}\n", quote(runs[runs.len() - 1].0));
+    fs::write(&list, entries)?;
+
+    let filter = match scale {
+        Some((width, height)) => format!("fps={},scale={}:{}", fps, width, height),
+        None => format!("fps={}", fps),
+    };
+    let script = format!(
+        "#!/bin/sh\ncd \"$(dirname \"$0\")\"\nffmpeg -f concat -safe 0 -i {} -vf {} -pix_fmt yuv420p {}\n",
+        quote(&file_name(&list)),
+        quote(&filter),
+        quote(&file_name(&output)),
+    );
+    fs::write(path, script)?;
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Single quotes `s` for both the shell and the concat list.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
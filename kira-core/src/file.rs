@@ -0,0 +1,276 @@
+use crate::{backend, Catalog, Graph, Timeline};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, fs::File, io, path::Path};
+
+mod comfy;
+mod png;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Kira,
+    ComfyWorkflow,
+    Png,
+}
+
+impl FileFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "kira" => Some(FileFormat::Kira),
+            "json" => Some(FileFormat::ComfyWorkflow),
+            "png" => Some(FileFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FileError {
+    UnknownFormat(String),
+    MissingWorkflow,
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::UnknownFormat(path) => write!(f, "unknown file format: {}", path),
+            FileError::MissingWorkflow => write!(f, "image does not contain a workflow"),
+            FileError::Io(e) => write!(f, "{}", e),
+            FileError::Json(e) => write!(f, "invalid file contents: {}", e),
+        }
+    }
+}
+
+impl Error for FileError {}
+
+impl From<io::Error> for FileError {
+    fn from(value: io::Error) -> Self {
+        FileError::Io(value)
+    }
+}
+impl From<serde_json::Error> for FileError {
+    fn from(value: serde_json::Error) -> Self {
+        FileError::Json(value)
+    }
+}
+
+/// A `.kira` file is the graph with the timeline alongside, files saved
+/// before there was a timeline hold just the graph.
+#[derive(Deserialize)]
+struct KiraFile {
+    #[serde(flatten)]
+    graph: Graph,
+    #[serde(default)]
+    timeline: Timeline,
+}
+
+#[derive(Serialize)]
+struct KiraFileRef<'a> {
+    #[serde(flatten)]
+    graph: &'a Graph,
+    #[serde(skip_serializing_if = "Timeline::is_default")]
+    timeline: &'a Timeline,
+}
+
+/// Reads the graph and timeline of a project, formats other than Kira only
+/// have a graph.
+pub fn read_project(path: &str) -> Result<(Graph, Timeline), FileError> {
+    let format =
+        FileFormat::from_path(path).ok_or_else(|| FileError::UnknownFormat(path.into()))?;
+    match format {
+        FileFormat::Kira => {
+            let f = File::open(path)?;
+            let file: KiraFile = serde_json::from_reader(f)?;
+            Ok((file.graph, file.timeline))
+        }
+        FileFormat::ComfyWorkflow => {
+            let f = File::open(path)?;
+            let workflow: comfy::Workflow = serde_json::from_reader(f)?;
+            Ok((workflow.into_graph(), Timeline::default()))
+        }
+        FileFormat::Png => {
+            let bytes = std::fs::read(path)?;
+            let text = png::text_chunk(&bytes, "workflow").ok_or(FileError::MissingWorkflow)?;
+            let workflow: comfy::Workflow = serde_json::from_str(&text)?;
+            Ok((workflow.into_graph(), Timeline::default()))
+        }
+    }
+}
+
+pub fn save_project(path: &str, graph: &Graph, timeline: &Timeline) -> Result<(), FileError> {
+    let f = File::create(path)?;
+    serde_json::to_writer(f, &KiraFileRef { graph, timeline })?;
+    Ok(())
+}
+
+/// Reads a catalog saved from the `object_info` endpoint of a backend, for
+/// working with graphs while it is offline.
+pub fn read_catalog(path: &str) -> Result<Catalog, FileError> {
+    let f = File::open(path)?;
+    Ok(backend::into_catalog(serde_json::from_reader(f)?))
+}
+
+/// Exports `graph` as a ComfyUI workflow, `nodes` provides the slots of each
+/// node type and `sizes` the rendered size of each node.
+pub fn save_workflow(
+    path: &str,
+    graph: &Graph,
+    nodes: &Catalog,
+    sizes: &[(f32, f32)],
+) -> Result<(), FileError> {
+    let f = File::create(path)?;
+    serde_json::to_writer(f, &comfy::Workflow::from_graph(graph, nodes, sizes))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend, prompt, Interpolation, Keyframe};
+    use serde_json::{json, Value};
+
+    fn node_info(inputs: Value, order: &[&str], outputs: &[&str]) -> Value {
+        json!({
+            "input": { "required": inputs },
+            "input_order": { "required": order },
+            "output": outputs,
+            "output_is_list": outputs.iter().map(|_| false).collect::<Vec<_>>(),
+            "output_name": outputs,
+            "name": "",
+            "display_name": "",
+            "description": "",
+            "category": "",
+            "output_node": false,
+        })
+    }
+
+    fn catalog() -> Catalog {
+        let info = json!({
+            "CheckpointLoaderSimple": node_info(
+                json!({ "ckpt_name": [["model.safetensors", "other.safetensors"]] }),
+                &["ckpt_name"],
+                &["MODEL", "CLIP", "VAE"],
+            ),
+            "CLIPTextEncode": node_info(
+                json!({ "text": ["STRING", { "multiline": true }], "clip": ["CLIP"] }),
+                &["text", "clip"],
+                &["CONDITIONING"],
+            ),
+            "EmptyLatentImage": node_info(
+                json!({
+                    "width": ["INT", { "default": 512 }],
+                    "height": ["INT", { "default": 512 }],
+                    "batch_size": ["INT", { "default": 1 }],
+                }),
+                &["width", "height", "batch_size"],
+                &["LATENT"],
+            ),
+            "KSampler": node_info(
+                json!({
+                    "model": ["MODEL"],
+                    "seed": ["INT", { "default": 0 }],
+                    "steps": ["INT", { "default": 20 }],
+                    "cfg": ["FLOAT", { "default": 8.0 }],
+                    "sampler_name": [["euler", "dpmpp_2m"]],
+                    "scheduler": [["normal", "karras"]],
+                    "positive": ["CONDITIONING"],
+                    "negative": ["CONDITIONING"],
+                    "latent_image": ["LATENT"],
+                    "denoise": ["FLOAT", { "default": 1.0 }],
+                }),
+                &[
+                    "model", "seed", "steps", "cfg", "sampler_name", "scheduler", "positive",
+                    "negative", "latent_image", "denoise",
+                ],
+                &["LATENT"],
+            ),
+        });
+        backend::into_catalog(serde_json::from_value(info).unwrap())
+    }
+
+    fn workflow() -> comfy::Workflow {
+        serde_json::from_value(json!({
+            "nodes": [
+                {
+                    "id": 4, "type": "CheckpointLoaderSimple", "pos": [0, 0], "size": [315, 98],
+                    "widgets_values": ["other.safetensors"],
+                },
+                {
+                    "id": 6, "type": "CLIPTextEncode", "pos": [400, 0],
+                    "widgets_values": ["a cat"],
+                },
+                {
+                    "id": 5, "type": "EmptyLatentImage", "pos": [400, 200],
+                    "widgets_values": [768, 512, 1],
+                },
+                {
+                    "id": 3, "type": "KSampler", "pos": [800, 0],
+                    // the seed is followed by its "control after generate"
+                    // choice, denoise is left to its default
+                    "widgets_values": [42, "fixed", 30, 6.5, "dpmpp_2m", "karras"],
+                },
+            ],
+            "links": [
+                [1, 4, 0, 3, 0, "MODEL"],
+                [2, 4, 1, 6, 0, "CLIP"],
+                [3, 6, 0, 3, 1, "CONDITIONING"],
+                [4, 6, 0, 3, 2, "CONDITIONING"],
+                [5, 5, 0, 3, 3, "LATENT"],
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn prompt_from_workflow_has_widget_values() {
+        let prompt = prompt::build(&workflow().into_graph(), &catalog(), 0.);
+        assert_eq!(
+            prompt,
+            json!({
+                "0": {
+                    "class_type": "CheckpointLoaderSimple",
+                    "inputs": { "ckpt_name": "other.safetensors" },
+                },
+                "1": {
+                    "class_type": "CLIPTextEncode",
+                    "inputs": { "text": "a cat", "clip": ["0", 1] },
+                },
+                "2": {
+                    "class_type": "EmptyLatentImage",
+                    "inputs": { "width": 768, "height": 512, "batch_size": 1 },
+                },
+                "3": {
+                    "class_type": "KSampler",
+                    "inputs": {
+                        "seed": 42,
+                        "steps": 30,
+                        "cfg": 6.5,
+                        "sampler_name": "dpmpp_2m",
+                        "scheduler": "karras",
+                        "denoise": 1.0,
+                        "model": ["0", 0],
+                        "positive": ["1", 0],
+                        "negative": ["1", 0],
+                        "latent_image": ["2", 0],
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn keyframes_override_widget_values() {
+        let mut graph = workflow().into_graph();
+        let keyframe = Keyframe {
+            time: 0.,
+            value: json!(10),
+            interpolation: Interpolation::Step,
+        };
+        graph.set_keyframe(3, "steps".into(), keyframe);
+        let prompt = prompt::build(&graph, &catalog(), 0.);
+        assert_eq!(prompt["3"]["inputs"]["steps"], json!(10));
+        assert_eq!(prompt["3"]["inputs"]["seed"], json!(42));
+    }
+}
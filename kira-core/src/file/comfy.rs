@@ -0,0 +1,327 @@
+use crate::{catalog, Catalog, Graph, Group, Link, LinkType, NodeMode, NodeType};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+const DEFAULT_GROUP_COLOR: &str = "#3f789e";
+const GROUP_FONT_SIZE: f32 = 24.;
+const WORKFLOW_VERSION: f32 = 0.4;
+
+/// LiteGraph serialization used by the ComfyUI frontend for saved workflows.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Workflow {
+    #[serde(default)]
+    pub last_node_id: i64,
+    #[serde(default)]
+    pub last_link_id: i64,
+    pub nodes: Vec<WorkflowNode>,
+    pub links: Vec<WorkflowLink>,
+    #[serde(default)]
+    pub groups: Vec<WorkflowGroup>,
+    #[serde(default)]
+    pub config: Map<String, Value>,
+    #[serde(default)]
+    pub extra: Map<String, Value>,
+    #[serde(default)]
+    pub version: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowNode {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub pos: Pos,
+    #[serde(default)]
+    pub size: Option<Pos>,
+    #[serde(default)]
+    pub flags: Map<String, Value>,
+    #[serde(default)]
+    pub order: usize,
+    #[serde(default)]
+    pub mode: i64,
+    #[serde(default)]
+    pub inputs: Vec<WorkflowInput>,
+    #[serde(default)]
+    pub outputs: Vec<WorkflowOutput>,
+    #[serde(default)]
+    pub properties: Map<String, Value>,
+    #[serde(default)]
+    pub widgets_values: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowInput {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Value,
+    pub link: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowOutput {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Value,
+    pub links: Option<Vec<i64>>,
+    #[serde(default)]
+    pub slot_index: usize,
+}
+
+/// `[id, src_node, src_slot, dst_node, dst_slot, type]`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowLink(
+    pub i64,
+    pub i64,
+    pub usize,
+    pub i64,
+    pub usize,
+    pub serde_json::Value,
+);
+
+/// `bounding` is `[x, y, width, height]`, including the title bar.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowGroup {
+    #[serde(default)]
+    pub title: String,
+    pub bounding: [f32; 4],
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub font_size: Option<f32>,
+}
+
+/// Older versions of the frontend serialize positions as `{"0": x, "1": y}`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Pos {
+    Array([f32; 2]),
+    Object {
+        #[serde(rename = "0")]
+        x: f32,
+        #[serde(rename = "1")]
+        y: f32,
+    },
+}
+
+impl From<Pos> for (f32, f32) {
+    fn from(value: Pos) -> Self {
+        match value {
+            Pos::Array([x, y]) | Pos::Object { x, y } => (x, y),
+        }
+    }
+}
+
+impl Workflow {
+    pub fn into_graph(self) -> Graph {
+        let mut graph = Graph::new();
+        let mut indices = HashMap::new();
+        for node in self.nodes {
+            let ty = NodeType(node.ty);
+            let is_note = ty.is_note() || ty.0 == "MarkdownNote";
+            let idx =
+                graph.add_node_at(if is_note { NodeType::note() } else { ty }, node.pos.into());
+            if is_note {
+                if let Some(text) = node.widgets_values.get(0).and_then(Value::as_str) {
+                    graph.set_node_text(idx, text.into());
+                }
+            } else {
+                graph.set_node_widgets(idx, node.widgets_values);
+            }
+            if let Some(size) = node.size {
+                graph.set_node_size(idx, size.into());
+            }
+            graph.set_node_mode(idx, NodeMode::from_comfy(node.mode));
+            indices.insert(node.id, idx);
+        }
+        for WorkflowLink(_, src, src_slot, dst, dst_slot, ty) in self.links {
+            if let (Some(&src_node), Some(&dst_node)) = (indices.get(&src), indices.get(&dst)) {
+                graph.add_link(Link {
+                    src_node,
+                    src_slot,
+                    dst_node,
+                    dst_slot,
+                    ty: LinkType(ty.as_str().unwrap_or("*").into()),
+                });
+            }
+        }
+        for group in self.groups {
+            let [x, y, w, h] = group.bounding;
+            graph.add_group(Group {
+                title: group.title,
+                color: group.color.unwrap_or_else(|| DEFAULT_GROUP_COLOR.into()),
+                rect: (x, y, w, h),
+            });
+        }
+        graph
+    }
+
+    /// Node ids are the graph indices plus one, since LiteGraph treats id 0
+    /// as unset. Slots of node types missing from `nodes` are recovered from
+    /// the links attached to them. Nodes opened from ComfyUI keep their size
+    /// there, the others take theirs from `sizes`.
+    pub fn from_graph(graph: &Graph, nodes: &Catalog, sizes: &[(f32, f32)]) -> Self {
+        let links = graph.get_links();
+        let workflow_nodes = graph
+            .get_nodes()
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| {
+                let (inputs, outputs) = match nodes.get(&node.ty) {
+                    Some(n) => (n.inputs.clone(), n.outputs.clone()),
+                    None => {
+                        let n = catalog::placeholder(&node.ty, idx, links);
+                        (n.inputs, n.outputs)
+                    }
+                };
+                let inputs = inputs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(slot, (name, ty))| WorkflowInput {
+                        name,
+                        ty: ty.0.into(),
+                        link: links
+                            .iter()
+                            .position(|l| l.dst_node == idx && l.dst_slot == slot)
+                            .map(|l| l as i64 + 1),
+                    })
+                    .collect();
+                let outputs = outputs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(slot, (name, ty))| {
+                        let attached = links
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, l)| l.src_node == idx && l.src_slot == slot)
+                            .map(|(l, _)| l as i64 + 1)
+                            .collect::<Vec<_>>();
+                        WorkflowOutput {
+                            name,
+                            ty: ty.0.into(),
+                            links: (!attached.is_empty()).then_some(attached),
+                            slot_index: slot,
+                        }
+                    })
+                    .collect();
+                let mut properties = Map::new();
+                let widgets_values = if node.ty.is_note() {
+                    properties.insert("text".into(), "".into());
+                    json!([node.text])
+                } else {
+                    properties.insert("Node name for S&R".into(), node.ty.0.clone().into());
+                    match &node.widgets {
+                        Value::Null => json!([]),
+                        widgets => widgets.clone(),
+                    }
+                };
+                // the size from ComfyUI fits its own rendering better
+                let size = node.size.or_else(|| sizes.get(idx).copied());
+                WorkflowNode {
+                    id: idx as i64 + 1,
+                    ty: node.ty.0.clone(),
+                    pos: Pos::Array([node.pos.0, node.pos.1]),
+                    size: size.map(|(w, h)| Pos::Array([w, h])),
+                    flags: Map::new(),
+                    order: idx,
+                    mode: node.mode.to_comfy(),
+                    inputs,
+                    outputs,
+                    properties,
+                    widgets_values,
+                }
+            })
+            .collect::<Vec<_>>();
+        let workflow_links = links
+            .iter()
+            .enumerate()
+            .map(|(idx, l)| {
+                WorkflowLink(
+                    idx as i64 + 1,
+                    l.src_node as i64 + 1,
+                    l.src_slot,
+                    l.dst_node as i64 + 1,
+                    l.dst_slot,
+                    l.ty.0.clone().into(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let groups = graph
+            .get_groups()
+            .iter()
+            .map(|g| WorkflowGroup {
+                title: g.title.clone(),
+                bounding: [g.rect.0, g.rect.1, g.rect.2, g.rect.3],
+                color: Some(g.color.clone()),
+                font_size: Some(GROUP_FONT_SIZE),
+            })
+            .collect();
+        Workflow {
+            last_node_id: workflow_nodes.len() as i64,
+            last_link_id: workflow_links.len() as i64,
+            nodes: workflow_nodes,
+            links: workflow_links,
+            groups,
+            config: Map::new(),
+            extra: Map::new(),
+            version: WORKFLOW_VERSION,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(graph: &Graph) -> Graph {
+        let workflow = Workflow::from_graph(graph, &Catalog::new(), &[]);
+        let json = serde_json::to_string(&workflow).unwrap();
+        serde_json::from_str::<Workflow>(&json)
+            .unwrap()
+            .into_graph()
+    }
+
+    #[test]
+    fn round_trips_through_workflows() {
+        let workflow: Workflow = serde_json::from_value(json!({
+            "nodes": [
+                {
+                    "id": 7, "type": "LoadImage", "pos": [0, 0], "size": [315, 314],
+                    "widgets_values": ["cat.png", "image"],
+                },
+                {
+                    "id": 3, "type": "ImageBlur", "pos": { "0": 400, "1": 0 }, "mode": 4,
+                    "widgets_values": { "radius": 2 },
+                },
+                { "id": 9, "type": "MarkdownNote", "pos": [0, 400], "widgets_values": ["# hi"] },
+            ],
+            "links": [[1, 7, 0, 3, 0, "IMAGE"]],
+            "groups": [{ "title": "Input", "bounding": [-10, -40, 340, 360] }],
+        }))
+        .unwrap();
+        let graph = workflow.into_graph();
+        for graph in [&graph, &round_trip(&graph)] {
+            let nodes = graph.get_nodes();
+            assert_eq!(nodes.len(), 3);
+            assert_eq!(nodes[0].ty, NodeType::from("LoadImage"));
+            assert_eq!(nodes[0].widgets, json!(["cat.png", "image"]));
+            assert_eq!(nodes[0].size, Some((315., 314.)));
+            assert_eq!(nodes[1].pos, (400., 0.));
+            assert_eq!(nodes[1].mode, NodeMode::Bypassed);
+            assert_eq!(nodes[1].widgets, json!({ "radius": 2 }));
+            assert!(nodes[2].ty.is_note());
+            assert_eq!(nodes[2].text, "# hi");
+
+            let links = graph.get_links();
+            assert_eq!(links.len(), 1);
+            assert_eq!((links[0].src_node, links[0].dst_node), (0, 1));
+            assert_eq!(links[0].ty.0, "IMAGE");
+
+            let groups = graph.get_groups();
+            assert_eq!(groups.len(), 1);
+            assert_eq!(groups[0].title, "Input");
+            assert_eq!(groups[0].color, DEFAULT_GROUP_COLOR);
+            assert_eq!(groups[0].rect, (-10., -40., 340., 360.));
+        }
+    }
+}
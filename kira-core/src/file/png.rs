@@ -0,0 +1,104 @@
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Looks up the value of an uncompressed `tEXt` or `iTXt` chunk, which is
+/// where ComfyUI embeds the workflow and prompt of a generated image.
+pub fn text_chunk(bytes: &[u8], keyword: &str) -> Option<String> {
+    let mut rest = bytes.strip_prefix(&SIGNATURE)?;
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[0..4].try_into().ok()?) as usize;
+        let ty = &rest[4..8];
+        let data = rest.get(8..8 + len)?;
+        match ty {
+            b"tEXt" => {
+                let (key, text) = split_nul(data)?;
+                if key == keyword.as_bytes() {
+                    // tEXt is latin-1, which maps 1:1 onto the first unicode code points
+                    return Some(text.iter().map(|&b| b as char).collect());
+                }
+            }
+            b"iTXt" => {
+                let (key, data) = split_nul(data)?;
+                if key == keyword.as_bytes() {
+                    let (compressed, data) = data.split_first()?;
+                    if *compressed != 0 {
+                        return None;
+                    }
+                    let (_language, data) = split_nul(data.get(1..)?)?;
+                    let (_translated, text) = split_nul(data)?;
+                    return String::from_utf8(text.to_vec()).ok();
+                }
+            }
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = rest.get(12 + len..)?;
+    }
+    None
+}
+
+fn split_nul(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let idx = data.iter().position(|&b| b == 0)?;
+    Some((&data[..idx], &data[idx + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PNG with the given chunks, the CRCs are left out as nothing
+    /// checks them.
+    fn png(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = SIGNATURE.to_vec();
+        for (ty, data) in chunks.iter().chain([&(b"IEND", vec![])]) {
+            bytes.extend((data.len() as u32).to_be_bytes());
+            bytes.extend(*ty);
+            bytes.extend(data);
+            bytes.extend([0; 4]);
+        }
+        bytes
+    }
+
+    fn text(keyword: &str, text: &[u8]) -> (&'static [u8; 4], Vec<u8>) {
+        (b"tEXt", [keyword.as_bytes(), b"\0", text].concat())
+    }
+
+    fn itext(keyword: &str, compressed: u8, text: &str) -> (&'static [u8; 4], Vec<u8>) {
+        let data = [
+            keyword.as_bytes(),
+            &[0, compressed, 0],
+            b"en\0",
+            b"Workflow\0",
+            text.as_bytes(),
+        ];
+        (b"iTXt", data.concat())
+    }
+
+    #[test]
+    fn reads_text_chunks() {
+        let bytes = png(&[
+            (b"IHDR", vec![0; 13]),
+            text("prompt", b"{}"),
+            text("workflow", b"caf\xe9"),
+        ]);
+        assert_eq!(text_chunk(&bytes, "workflow").as_deref(), Some("café"));
+        assert_eq!(text_chunk(&bytes, "prompt").as_deref(), Some("{}"));
+        assert_eq!(text_chunk(&bytes, "other"), None);
+    }
+
+    #[test]
+    fn reads_uncompressed_international_text_chunks() {
+        let bytes = png(&[itext("workflow", 0, "café")]);
+        assert_eq!(text_chunk(&bytes, "workflow").as_deref(), Some("café"));
+        let bytes = png(&[itext("workflow", 1, "café")]);
+        assert_eq!(text_chunk(&bytes, "workflow"), None);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_image() {
+        let mut bytes = png(&[]);
+        let trailing = png(&[text("workflow", b"{}")]);
+        bytes.extend(&trailing[SIGNATURE.len()..]);
+        assert_eq!(text_chunk(&bytes, "workflow"), None);
+        assert_eq!(text_chunk(b"GIF89a", "workflow"), None);
+    }
+}
@@ -0,0 +1,539 @@
+use super::keyframe::{self, Keyframe};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+const FIT_MARGIN: f32 = 20.;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.;
+/// Bookmarks can be bound to the number keys 1 to 9.
+const BOOKMARK_KEYS: std::ops::RangeInclusive<u8> = 1..=9;
+/// Colours offered for groups, the same presets as the ComfyUI frontend.
+pub const GROUP_COLORS: &[&str] = &[
+    "#3f789e", "#a88", "#b06634", "#8a8", "#88a", "#8aa", "#a1309b", "#b58b2a", "#444",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Graph {
+    nodes: Vec<NodeInstance>,
+    links: Vec<Link>,
+    zoom: f32,
+    offset: (f32, f32),
+    #[serde(default)]
+    groups: Vec<Group>,
+    /// Definitions of the subgraphs used in the project, only filled in on
+    /// the top level graph.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    subgraphs: BTreeMap<String, Subgraph>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bookmarks: Vec<Bookmark>,
+    #[serde(skip)]
+    selection: BTreeSet<usize>,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            links: vec![],
+            zoom: 2.,
+            offset: (0., 0.),
+            groups: vec![],
+            subgraphs: BTreeMap::new(),
+            bookmarks: vec![],
+            selection: BTreeSet::new(),
+        }
+    }
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+    pub fn offset(&self) -> (f32, f32) {
+        self.offset
+    }
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom;
+    }
+    pub fn set_offset(&mut self, offset: (f32, f32)) {
+        self.offset = offset;
+    }
+    /// Centers the view on `bounds` (`(x, y, width, height)`) and zooms so it
+    /// fits in a viewport of the given size.
+    pub fn fit_view(&mut self, bounds: (f32, f32, f32, f32), viewport: (f32, f32)) {
+        let (x, y, w, h) = bounds;
+        let zoom = (viewport.0 / (w + 2. * FIT_MARGIN))
+            .min(viewport.1 / (h + 2. * FIT_MARGIN))
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom = zoom;
+        self.offset = (
+            viewport.0 / (2. * zoom) - (x + w / 2.),
+            viewport.1 / (2. * zoom) - (y + h / 2.),
+        );
+    }
+    /// Scrolls so `point` is in the middle of a viewport of the given size,
+    /// keeping the zoom.
+    pub fn center_on(&mut self, point: (f32, f32), viewport: (f32, f32)) {
+        self.offset = (
+            viewport.0 / (2. * self.zoom) - point.0,
+            viewport.1 / (2. * self.zoom) - point.1,
+        );
+    }
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+    pub fn bookmark(&self, name: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|b| b.name == name)
+    }
+    pub fn bookmark_with_key(&self, key: u8) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|b| b.key == Some(key))
+    }
+    /// Remembers the current view under `name`, replacing the bookmark of
+    /// that name. New bookmarks get the first free number key unless `key`
+    /// is given, which is then taken from any other bookmark.
+    pub fn save_bookmark(&mut self, name: String, key: Option<u8>) {
+        if let Some(key) = key {
+            for bookmark in self.bookmarks.iter_mut().filter(|b| b.name != name) {
+                if bookmark.key == Some(key) {
+                    bookmark.key = None;
+                }
+            }
+        }
+        let (zoom, offset) = (self.zoom, self.offset);
+        match self.bookmarks.iter_mut().find(|b| b.name == name) {
+            Some(bookmark) => {
+                bookmark.zoom = zoom;
+                bookmark.offset = offset;
+                bookmark.key = key.or(bookmark.key);
+            }
+            None => {
+                let key = key.or_else(|| {
+                    BOOKMARK_KEYS
+                        .clone()
+                        .find(|k| self.bookmark_with_key(*k).is_none())
+                });
+                self.bookmarks.push(Bookmark {
+                    name,
+                    zoom,
+                    offset,
+                    key,
+                });
+            }
+        }
+    }
+    pub fn go_to_bookmark(&mut self, name: &str) {
+        if let Some(bookmark) = self.bookmark(name) {
+            (self.zoom, self.offset) = (bookmark.zoom, bookmark.offset);
+        }
+    }
+    pub fn add_node_at(&mut self, id: NodeType, pos: (f32, f32)) -> usize {
+        self.add_node(NodeInstance {
+            ty: id,
+            pos,
+            text: String::new(),
+            mode: NodeMode::Active,
+            keyframes: BTreeMap::new(),
+            widgets: Value::Null,
+            size: None,
+            outputs: vec![],
+        })
+    }
+    pub fn add_node(&mut self, node: NodeInstance) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+    pub fn set_node_position(&mut self, node_idx: usize, x: f32, y: f32) {
+        let node_ref = self.nodes.get_mut(node_idx).unwrap();
+        node_ref.pos.0 = x;
+        node_ref.pos.1 = y;
+    }
+    pub fn set_node_text(&mut self, node_idx: usize, text: String) {
+        if let Some(node) = self.nodes.get_mut(node_idx) {
+            node.text = text;
+        }
+    }
+    pub fn set_node_type(&mut self, node_idx: usize, ty: NodeType) {
+        if let Some(node) = self.nodes.get_mut(node_idx) {
+            node.ty = ty;
+        }
+    }
+    pub fn set_node_widgets(&mut self, node_idx: usize, widgets: Value) {
+        if let Some(node) = self.nodes.get_mut(node_idx) {
+            node.widgets = widgets;
+        }
+    }
+    pub fn set_node_size(&mut self, node_idx: usize, size: (f32, f32)) {
+        if let Some(node) = self.nodes.get_mut(node_idx) {
+            node.size = Some(size);
+        }
+    }
+    pub fn set_node_mode(&mut self, node_idx: usize, mode: NodeMode) {
+        if let Some(node) = self.nodes.get_mut(node_idx) {
+            node.mode = mode;
+        }
+    }
+    pub fn set_keyframe(&mut self, node_idx: usize, widget: String, keyframe: Keyframe) {
+        if let Some(node) = self.nodes.get_mut(node_idx) {
+            keyframe::insert(node.keyframes.entry(widget).or_default(), keyframe);
+        }
+    }
+    /// Removes the keyframes of a node within `tolerance` of `time`.
+    pub fn remove_keyframes(&mut self, node_idx: usize, time: f32, tolerance: f32) {
+        if let Some(node) = self.nodes.get_mut(node_idx) {
+            for keys in node.keyframes.values_mut() {
+                keys.retain(|k| (k.time - time).abs() > tolerance);
+            }
+            node.keyframes.retain(|_, keys| !keys.is_empty());
+        }
+    }
+    pub fn set_node_outputs(&mut self, node_idx: usize, outputs: Vec<String>) {
+        if let Some(node) = self.nodes.get_mut(node_idx) {
+            node.outputs = outputs;
+        }
+    }
+    /// Removes nodes along with their links, the remaining nodes keep their
+    /// order.
+    pub fn remove_nodes(&mut self, indices: &BTreeSet<usize>) {
+        let remap =
+            |idx: usize| (!indices.contains(&idx)).then(|| idx - indices.range(..idx).count());
+        let mut idx = 0;
+        self.nodes.retain(|_| {
+            idx += 1;
+            !indices.contains(&(idx - 1))
+        });
+        self.links = std::mem::take(&mut self.links)
+            .into_iter()
+            .filter_map(|l| {
+                Some(Link {
+                    src_node: remap(l.src_node)?,
+                    dst_node: remap(l.dst_node)?,
+                    ..l
+                })
+            })
+            .collect();
+        self.selection = self.selection.iter().filter_map(|i| remap(*i)).collect();
+    }
+    /// Adds the nodes, links and groups of `other` moved by `delta`, and
+    /// returns the indices of the new nodes. Subgraph definitions are left
+    /// out.
+    pub fn append(&mut self, mut other: Graph, delta: (f32, f32)) -> Vec<usize> {
+        let first = self.nodes.len();
+        other.translate(delta);
+        self.nodes.extend(other.nodes);
+        self.links.extend(other.links.into_iter().map(|l| Link {
+            src_node: l.src_node + first,
+            dst_node: l.dst_node + first,
+            ..l
+        }));
+        self.groups.extend(other.groups);
+        (first..self.nodes.len()).collect()
+    }
+    /// Moves every node and group by `delta`.
+    pub fn translate(&mut self, delta: (f32, f32)) {
+        for node in &mut self.nodes {
+            node.pos = (node.pos.0 + delta.0, node.pos.1 + delta.1);
+        }
+        for group in &mut self.groups {
+            group.rect.0 += delta.0;
+            group.rect.1 += delta.1;
+        }
+    }
+    pub fn remove_link(&mut self, idx: usize) {
+        self.links.remove(idx);
+    }
+    pub fn add_link(&mut self, lnk: Link) {
+        self.links.push(lnk);
+    }
+    pub fn get_nodes(&self) -> &[NodeInstance] {
+        &self.nodes
+    }
+    pub fn get_node(&self, idx: usize) -> Option<&NodeInstance> {
+        self.nodes.get(idx)
+    }
+    pub fn get_links(&self) -> &[Link] {
+        &self.links
+    }
+    pub fn subgraphs(&self) -> &BTreeMap<String, Subgraph> {
+        &self.subgraphs
+    }
+    pub fn subgraph(&self, name: &str) -> Option<&Subgraph> {
+        self.subgraphs.get(name)
+    }
+    pub fn subgraph_mut(&mut self, name: &str) -> Option<&mut Subgraph> {
+        self.subgraphs.get_mut(name)
+    }
+    pub fn add_subgraph(&mut self, name: String, subgraph: Subgraph) {
+        self.subgraphs.insert(name, subgraph);
+    }
+    pub fn take_subgraphs(&mut self) -> BTreeMap<String, Subgraph> {
+        std::mem::take(&mut self.subgraphs)
+    }
+    pub fn add_group(&mut self, group: Group) -> usize {
+        self.groups.push(group);
+        self.groups.len() - 1
+    }
+    pub fn get_groups(&self) -> &[Group] {
+        &self.groups
+    }
+    pub fn get_group(&self, idx: usize) -> Option<&Group> {
+        self.groups.get(idx)
+    }
+    pub fn set_group_rect(&mut self, idx: usize, rect: (f32, f32, f32, f32)) {
+        if let Some(group) = self.groups.get_mut(idx) {
+            group.rect = rect;
+        }
+    }
+    pub fn set_group_title(&mut self, idx: usize, title: String) {
+        if let Some(group) = self.groups.get_mut(idx) {
+            group.title = title;
+        }
+    }
+    /// Switches a group to the next preset colour.
+    pub fn cycle_group_color(&mut self, idx: usize) {
+        if let Some(group) = self.groups.get_mut(idx) {
+            let next = GROUP_COLORS
+                .iter()
+                .position(|c| c.eq_ignore_ascii_case(&group.color))
+                .map_or(0, |i| (i + 1) % GROUP_COLORS.len());
+            group.color = GROUP_COLORS[next].into();
+        }
+    }
+    pub fn remove_group(&mut self, idx: usize) {
+        if idx < self.groups.len() {
+            self.groups.remove(idx);
+        }
+    }
+    pub fn selection(&self) -> &BTreeSet<usize> {
+        &self.selection
+    }
+    pub fn is_selected(&self, idx: usize) -> bool {
+        self.selection.contains(&idx)
+    }
+    /// Selects a single node, or toggles it when extending the selection.
+    pub fn select_node(&mut self, idx: usize, extend: bool) {
+        if !extend {
+            self.selection.clear();
+            self.selection.insert(idx);
+        } else if !self.selection.remove(&idx) {
+            self.selection.insert(idx);
+        }
+    }
+    pub fn set_selection(&mut self, selection: impl IntoIterator<Item = usize>) {
+        let len = self.nodes.len();
+        self.selection = selection.into_iter().filter(|idx| *idx < len).collect();
+    }
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+}
+
+/// Smallest `(x, y, width, height)` rectangle containing all of `rects`.
+pub fn bounding_box(
+    rects: impl IntoIterator<Item = (f32, f32, f32, f32)>,
+) -> Option<(f32, f32, f32, f32)> {
+    rects
+        .into_iter()
+        .map(|(x, y, w, h)| (x, y, x + w, y + h))
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+        .map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0, y1 - y0))
+}
+
+/// Whether `inner` lies entirely within `outer`, both as `(x, y, width, height)`.
+pub fn contains(outer: (f32, f32, f32, f32), inner: (f32, f32, f32, f32)) -> bool {
+    inner.0 >= outer.0
+        && inner.1 >= outer.1
+        && inner.0 + inner.2 <= outer.0 + outer.2
+        && inner.1 + inner.3 <= outer.1 + outer.3
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeInstance {
+    pub ty: NodeType,
+    pub pos: (f32, f32),
+    /// Contents of note nodes.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    #[serde(default, skip_serializing_if = "NodeMode::is_active")]
+    pub mode: NodeMode,
+    /// Widgets varying over the timeline, by input name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keyframes: BTreeMap<String, Vec<Keyframe>>,
+    /// Values of the widgets as ComfyUI workflows store them: a list in the
+    /// order the node type declares its widgets, or an object by name for
+    /// some custom nodes.
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub widgets: Value,
+    /// Size of the node in the ComfyUI workflow it was opened from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<(f32, f32)>,
+    /// Paths of the images the node produced the last time it ran.
+    #[serde(skip)]
+    pub outputs: Vec<String>,
+}
+
+/// Whether a node takes part in execution, as in the ComfyUI frontend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeMode {
+    #[default]
+    Active,
+    /// Left out of the prompt along with the links leaving it.
+    Muted,
+    /// Left out of the prompt, each of its outputs is fed by the input of
+    /// the same type instead.
+    Bypassed,
+}
+
+impl NodeMode {
+    pub fn is_active(&self) -> bool {
+        *self == NodeMode::Active
+    }
+    /// Reads the workflow `mode`, 2 is "never" and 4 "bypass".
+    pub fn from_comfy(mode: i64) -> Self {
+        match mode {
+            2 => NodeMode::Muted,
+            4 => NodeMode::Bypassed,
+            _ => NodeMode::Active,
+        }
+    }
+    pub fn to_comfy(self) -> i64 {
+        match self {
+            NodeMode::Active => 0,
+            NodeMode::Muted => 2,
+            NodeMode::Bypassed => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Link {
+    pub src_node: usize,
+    pub src_slot: usize,
+    pub dst_node: usize,
+    pub dst_slot: usize,
+    pub ty: LinkType,
+}
+
+/// A named view of the graph.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub zoom: f32,
+    pub offset: (f32, f32),
+    /// Number key recalling the bookmark.
+    #[serde(default)]
+    pub key: Option<u8>,
+}
+
+/// A titled frame drawn behind the nodes it contains, `rect` includes the
+/// title bar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub title: String,
+    pub color: String,
+    pub rect: (f32, f32, f32, f32),
+}
+
+/// A graph used as a single node, each of its slots stands for a slot of
+/// one of the inner nodes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subgraph {
+    pub graph: Graph,
+    pub inputs: Vec<Port>,
+    pub outputs: Vec<Port>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Port {
+    pub name: String,
+    pub ty: LinkType,
+    pub node: usize,
+    pub slot: usize,
+}
+
+/// A link attached to a single slot, waiting for its other end.
+#[derive(Debug, Clone)]
+pub enum DanglingLink {
+    FromOutput {
+        node: usize,
+        slot: usize,
+        ty: LinkType,
+    },
+    ToInput {
+        node: usize,
+        slot: usize,
+        ty: LinkType,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkType(pub String);
+
+impl LinkType {
+    /// Wildcard type of slots that adapt to whatever they are linked to.
+    pub fn any() -> Self {
+        LinkType("*".into())
+    }
+    pub fn is_any(&self) -> bool {
+        self.0 == "*"
+    }
+    pub fn accepts(&self, other: &LinkType) -> bool {
+        self.is_any() || other.is_any() || self.0 == other.0
+    }
+    /// The more specific of two compatible types.
+    pub fn resolve(&self, other: &LinkType) -> LinkType {
+        if self.is_any() {
+            other.clone()
+        } else {
+            self.clone()
+        }
+    }
+}
+
+impl From<&str> for LinkType {
+    fn from(value: &str) -> Self {
+        LinkType(value.into())
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeType(pub String);
+
+impl NodeType {
+    /// Built-in free text node, named like its ComfyUI counterpart.
+    pub fn note() -> Self {
+        NodeType("Note".into())
+    }
+    pub fn is_note(&self) -> bool {
+        self.0 == "Note"
+    }
+    /// Built-in pass-through node with a single input and output.
+    pub fn reroute() -> Self {
+        NodeType("Reroute".into())
+    }
+    pub fn is_reroute(&self) -> bool {
+        self.0 == "Reroute"
+    }
+    /// Node standing for the subgraph definition `name`.
+    pub fn subgraph(name: &str) -> Self {
+        NodeType(format!("subgraph:{}", name))
+    }
+    pub fn subgraph_name(&self) -> Option<&str> {
+        self.0.strip_prefix("subgraph:")
+    }
+}
+
+impl From<&str> for NodeType {
+    fn from(value: &str) -> Self {
+        NodeType(value.into())
+    }
+}
+impl From<String> for NodeType {
+    fn from(value: String) -> Self {
+        NodeType(value)
+    }
+}
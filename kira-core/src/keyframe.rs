@@ -0,0 +1,134 @@
+//! Widget values changing over the timeline.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How the value moves from a keyframe to the next one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// Holds the value until the next keyframe.
+    #[default]
+    Step,
+    Linear,
+    /// Starts and ends slowly.
+    Ease,
+}
+
+impl Interpolation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "step" => Some(Interpolation::Step),
+            "linear" => Some(Interpolation::Linear),
+            "ease" => Some(Interpolation::Ease),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyframe {
+    /// In seconds.
+    pub time: f32,
+    pub value: Value,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+/// Adds `keyframe` to `keys`, sorted by time, replacing any keyframe at the
+/// same time.
+pub fn insert(keys: &mut Vec<Keyframe>, keyframe: Keyframe) {
+    keys.retain(|k| k.time != keyframe.time);
+    let idx = keys.partition_point(|k| k.time < keyframe.time);
+    keys.insert(idx, keyframe);
+}
+
+/// The value of `keys` at time `t`. Only numbers are interpolated, other
+/// values change at the next keyframe, and integers stay integers.
+pub fn value_at(keys: &[Keyframe], t: f32) -> Option<Value> {
+    let next = keys.partition_point(|k| k.time <= t);
+    let Some(prev) = next.checked_sub(1).map(|i| &keys[i]) else {
+        // before the first keyframe
+        return keys.first().map(|k| k.value.clone());
+    };
+    let Some(next) = keys.get(next) else {
+        return Some(prev.value.clone());
+    };
+    let (Some(a), Some(b)) = (prev.value.as_f64(), next.value.as_f64()) else {
+        return Some(prev.value.clone());
+    };
+    let f = ((t - prev.time) / (next.time - prev.time)) as f64;
+    let f = match prev.interpolation {
+        Interpolation::Step => return Some(prev.value.clone()),
+        Interpolation::Linear => f,
+        Interpolation::Ease => f * f * (3. - 2. * f),
+    };
+    let v = a + (b - a) * f;
+    if prev.value.is_i64() && next.value.is_i64() {
+        Some(Value::from(v.round() as i64))
+    } else if prev.value.is_u64() && next.value.is_u64() {
+        Some(Value::from(v.round() as u64))
+    } else {
+        Some(Value::from(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn keys(a: Value, b: Value, interpolation: Interpolation) -> Vec<Keyframe> {
+        vec![
+            Keyframe {
+                time: 1.,
+                value: a,
+                interpolation,
+            },
+            Keyframe {
+                time: 3.,
+                value: b,
+                interpolation,
+            },
+        ]
+    }
+
+    #[test]
+    fn step_holds_until_the_next_keyframe() {
+        let keys = keys(json!(10), json!(20), Interpolation::Step);
+        assert_eq!(value_at(&keys, 1.), Some(json!(10)));
+        assert_eq!(value_at(&keys, 2.9), Some(json!(10)));
+        assert_eq!(value_at(&keys, 3.), Some(json!(20)));
+    }
+
+    #[test]
+    fn linear_keeps_integers_integers() {
+        let ints = keys(json!(10), json!(20), Interpolation::Linear);
+        assert_eq!(value_at(&ints, 2.), Some(json!(15)));
+        assert_eq!(value_at(&ints, 1.5), Some(json!(13)));
+        let floats = keys(json!(1.), json!(2.), Interpolation::Linear);
+        assert_eq!(value_at(&floats, 1.5), Some(json!(1.25)));
+    }
+
+    #[test]
+    fn ease_starts_and_ends_slowly() {
+        let keys = keys(json!(0.), json!(1.), Interpolation::Ease);
+        assert_eq!(value_at(&keys, 1.5), Some(json!(0.15625)));
+        assert_eq!(value_at(&keys, 2.), Some(json!(0.5)));
+        assert_eq!(value_at(&keys, 2.5), Some(json!(0.84375)));
+    }
+
+    #[test]
+    fn only_numbers_are_interpolated() {
+        let keys = keys(json!("euler"), json!("karras"), Interpolation::Linear);
+        assert_eq!(value_at(&keys, 2.), Some(json!("euler")));
+    }
+
+    #[test]
+    fn outside_the_keyframes_holds_the_nearest() {
+        let keys = keys(json!(10), json!(20), Interpolation::Linear);
+        assert_eq!(value_at(&keys, 0.), Some(json!(10)));
+        assert_eq!(value_at(&keys, 5.), Some(json!(20)));
+        assert_eq!(value_at(&[], 1.), None);
+    }
+}
@@ -0,0 +1,280 @@
+//! Layered graph drawing in the style of Sugiyama et al: nodes are ranked
+//! into columns by link depth, columns are reordered to reduce crossings and
+//! finally spaced out according to the size of each node.
+
+use std::collections::HashMap;
+
+const LAYER_GAP: f32 = 60.;
+const NODE_GAP: f32 = 20.;
+const SWEEPS: usize = 8;
+
+/// `(x, y, width, height)` in graph coordinates.
+type Rect = (f32, f32, f32, f32);
+
+/// A vertex of the layered graph, long links are routed through dummies so
+/// crossing reduction can take them into account.
+#[derive(Debug, Clone, Copy)]
+enum Vertex {
+    Node(usize),
+    Dummy { height: f32 },
+}
+
+/// Computes new positions for `nodes`, given as `(id, (x, y, width, height))`.
+/// `links` are `(src, dst)` pairs of ids, those touching nodes outside of
+/// `nodes` are ignored. The result keeps the top-left corner of the
+/// original bounding box.
+pub fn layered(nodes: &[(usize, Rect)], links: &[(usize, usize)]) -> HashMap<usize, (f32, f32)> {
+    let local = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (id, _))| (*id, i))
+        .collect::<HashMap<_, _>>();
+    let mut edges = links
+        .iter()
+        .filter_map(|(src, dst)| Some((*local.get(src)?, *local.get(dst)?)))
+        .filter(|(src, dst)| src != dst)
+        .collect::<Vec<_>>();
+    edges.sort_unstable();
+    edges.dedup();
+
+    let ranks = rank(nodes.len(), &edges);
+
+    // split links spanning several ranks with dummy vertices
+    let mut vertices = (0..nodes.len()).map(Vertex::Node).collect::<Vec<_>>();
+    let mut vertex_rank = ranks.clone();
+    let mut vertex_y = nodes.iter().map(|(_, r)| r.1).collect::<Vec<_>>();
+    let mut layered_edges = vec![];
+    for &(src, dst) in &edges {
+        let (src, dst) = match ranks[src].cmp(&ranks[dst]) {
+            std::cmp::Ordering::Less => (src, dst),
+            std::cmp::Ordering::Greater => (dst, src),
+            std::cmp::Ordering::Equal => continue,
+        };
+        let mut prev = src;
+        for r in ranks[src] + 1..ranks[dst] {
+            vertices.push(Vertex::Dummy { height: NODE_GAP });
+            vertex_rank.push(r);
+            vertex_y.push(vertex_y[src]);
+            let dummy = vertices.len() - 1;
+            layered_edges.push((prev, dummy));
+            prev = dummy;
+        }
+        layered_edges.push((prev, dst));
+    }
+
+    // start from the current vertical order to keep the user's mental map
+    let layer_count = vertex_rank.iter().max().map_or(0, |r| r + 1);
+    let mut layers = vec![vec![]; layer_count];
+    for (v, r) in vertex_rank.iter().enumerate() {
+        layers[*r].push(v);
+    }
+    for layer in layers.iter_mut() {
+        layer.sort_by(|a, b| vertex_y[*a].total_cmp(&vertex_y[*b]));
+    }
+
+    let mut preds = vec![vec![]; vertices.len()];
+    let mut succs = vec![vec![]; vertices.len()];
+    for &(src, dst) in &layered_edges {
+        preds[dst].push(src);
+        succs[src].push(dst);
+    }
+
+    let mut best = layers.clone();
+    let mut best_crossings = crossings(&layers, &succs);
+    for sweep in 0..SWEEPS {
+        if sweep % 2 == 0 {
+            for i in 1..layers.len() {
+                let (above, below) = layers.split_at_mut(i);
+                reorder(&mut below[0], &above[i - 1], &preds);
+            }
+        } else {
+            for i in (0..layers.len().saturating_sub(1)).rev() {
+                let (above, below) = layers.split_at_mut(i + 1);
+                reorder(&mut above[i], &below[0], &succs);
+            }
+        }
+        let c = crossings(&layers, &succs);
+        if c < best_crossings {
+            best_crossings = c;
+            best = layers.clone();
+        }
+    }
+
+    // assign coordinates, columns are as wide as their widest node and
+    // centered vertically on the tallest column
+    let size = |v: usize| match vertices[v] {
+        Vertex::Node(i) => (nodes[i].1 .2, nodes[i].1 .3),
+        Vertex::Dummy { height } => (0., height),
+    };
+    let column_height = |layer: &Vec<usize>| {
+        layer.iter().map(|v| size(*v).1).sum::<f32>()
+            + NODE_GAP * layer.len().saturating_sub(1) as f32
+    };
+    let tallest = best.iter().map(column_height).fold(0., f32::max);
+    let origin = nodes.iter().fold((f32::MAX, f32::MAX), |acc, (_, r)| {
+        (acc.0.min(r.0), acc.1.min(r.1))
+    });
+
+    let mut positions = HashMap::new();
+    let mut x = origin.0;
+    for layer in &best {
+        let mut y = origin.1 + (tallest - column_height(layer)) / 2.;
+        let mut width = 0f32;
+        for &v in layer {
+            let (w, h) = size(v);
+            if let Vertex::Node(i) = vertices[v] {
+                positions.insert(nodes[i].0, (x, y));
+            }
+            y += h + NODE_GAP;
+            width = width.max(w);
+        }
+        x += width + LAYER_GAP;
+    }
+    positions
+}
+
+/// Longest path ranking, cycles are broken by picking the node with the
+/// fewest unranked predecessors.
+fn rank(count: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut indegree = vec![0; count];
+    let mut succs = vec![vec![]; count];
+    for &(src, dst) in edges {
+        indegree[dst] += 1;
+        succs[src].push(dst);
+    }
+    let mut ranks = vec![0; count];
+    let mut done = vec![false; count];
+    let mut queue = (0..count).filter(|v| indegree[*v] == 0).collect::<Vec<_>>();
+    for _ in 0..count {
+        let v = match queue.pop() {
+            Some(v) => v,
+            None => match (0..count)
+                .filter(|v| !done[*v])
+                .min_by_key(|v| indegree[*v])
+            {
+                Some(v) => v,
+                None => break,
+            },
+        };
+        if done[v] {
+            continue;
+        }
+        done[v] = true;
+        for &s in &succs[v] {
+            if done[s] {
+                continue;
+            }
+            ranks[s] = ranks[s].max(ranks[v] + 1);
+            indegree[s] -= 1;
+            if indegree[s] == 0 {
+                queue.push(s);
+            }
+        }
+    }
+    ranks
+}
+
+/// Sorts `layer` by the barycenter of each vertex's neighbours in `fixed`,
+/// vertices without neighbours keep their position.
+fn reorder(layer: &mut [usize], fixed: &[usize], neighbours: &[Vec<usize>]) {
+    let position = fixed
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (*v, i as f32))
+        .collect::<HashMap<_, _>>();
+    let mut keyed = layer
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let ps = neighbours[*v]
+                .iter()
+                .filter_map(|n| position.get(n))
+                .collect::<Vec<_>>();
+            let key = if ps.is_empty() {
+                i as f32
+            } else {
+                ps.iter().copied().sum::<f32>() / ps.len() as f32
+            };
+            (key, *v)
+        })
+        .collect::<Vec<_>>();
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (slot, (_, v)) in layer.iter_mut().zip(keyed) {
+        *slot = v;
+    }
+}
+
+fn crossings(layers: &[Vec<usize>], succs: &[Vec<usize>]) -> usize {
+    let mut total = 0;
+    for pair in layers.windows(2) {
+        let below = pair[1]
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (*v, i))
+            .collect::<HashMap<_, _>>();
+        let edges = pair[0]
+            .iter()
+            .enumerate()
+            .flat_map(|(i, v)| {
+                succs[*v]
+                    .iter()
+                    .filter_map(|s| Some((i, *below.get(s)?)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for (a, e) in edges.iter().enumerate() {
+            for f in &edges[a + 1..] {
+                if (e.0 < f.0 && e.1 > f.1) || (e.0 > f.0 && e.1 < f.1) {
+                    total += 1;
+                }
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_crossings() {
+        let succs = vec![vec![3], vec![2], vec![], vec![]];
+        assert_eq!(crossings(&[vec![0, 1], vec![2, 3]], &succs), 1);
+        assert_eq!(crossings(&[vec![0, 1], vec![3, 2]], &succs), 0);
+    }
+
+    #[test]
+    fn uncrosses_links() {
+        // 10 feeds 21 and 11 feeds 20, which sit the other way around
+        let nodes = [
+            (10, (0., 0., 100., 50.)),
+            (11, (0., 100., 100., 50.)),
+            (20, (300., 0., 100., 50.)),
+            (21, (300., 100., 100., 50.)),
+            (30, (600., 50., 100., 50.)),
+        ];
+        let links = [(10, 21), (11, 20), (20, 30), (21, 30)];
+        let positions = layered(&nodes, &links);
+        let y = |id: usize| positions[&id].1;
+        assert_eq!(y(10) < y(11), y(21) < y(20));
+        // one column per rank, starting where the nodes did
+        assert_eq!(positions[&10].0, 0.);
+        assert_eq!(positions[&20].0, 100. + LAYER_GAP);
+        assert_eq!(positions[&30].0, 2. * (100. + LAYER_GAP));
+        assert_eq!(y(10).min(y(11)), 0.);
+    }
+
+    #[test]
+    fn routes_long_links_through_dummies() {
+        // the link from 0 to 2 skips a rank and gets in the way of 1
+        let nodes = [
+            (0, (0., 0., 100., 50.)),
+            (1, (200., 0., 100., 50.)),
+            (2, (400., 0., 100., 50.)),
+        ];
+        let positions = layered(&nodes, &[(0, 1), (1, 2), (0, 2)]);
+        assert_eq!(positions[&2].0, 2. * (100. + LAYER_GAP));
+        assert_eq!(positions.len(), 3);
+    }
+}
@@ -0,0 +1,29 @@
+//! The graph model of kira, free of any user interface: graphs and their
+//! subgraphs, the catalog of node types, the file formats, the timeline,
+//! building and checking prompts, and the client of the backend running
+//! them.
+
+pub use self::{
+    backend::Backend,
+    catalog::{Catalog, Node, Widget},
+    export::{export_timeline, ExportError, ExportFormat, ExportOptions},
+    file::{read_catalog, read_project, save_project, save_workflow, FileError, FileFormat},
+    graph::{
+        DanglingLink, Graph, Group, Link, LinkType, NodeInstance, NodeMode, NodeType, Port,
+        Subgraph,
+    },
+    keyframe::{Interpolation, Keyframe},
+    timeline::{Segment, Timeline, Track},
+};
+
+pub mod align;
+pub mod backend;
+pub mod catalog;
+pub mod export;
+pub mod file;
+pub mod graph;
+pub mod keyframe;
+pub mod layout;
+pub mod prompt;
+pub mod subgraph;
+pub mod timeline;
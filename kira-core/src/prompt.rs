@@ -0,0 +1,302 @@
+//! The API prompts the backend executes, built from the top level graph of
+//! a project with its subgraphs flattened.
+
+use crate::{
+    catalog::{self, Catalog},
+    graph::{Graph, Link, NodeInstance, NodeMode},
+    keyframe, subgraph,
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+
+/// Builds the API prompt the backend executes, linked inputs reference
+/// their source as `[node_id, output_slot]`. Notes and reroutes only exist
+/// in the editor and are left out, inputs fed through reroutes are wired to
+/// the node behind them. Widgets take the values stored on their node, or
+/// their default, and keyframed widgets their value at `time`.
+pub fn build(root: &Graph, catalog: &Catalog, time: f32) -> Value {
+    build_flat(&subgraph::flatten(root), catalog, time)
+}
+
+/// One prompt per frame at `fps` from the first keyframe to the last, with
+/// the time of each frame.
+pub fn render_range(root: &Graph, catalog: &Catalog, fps: f32) -> Vec<(f32, Value)> {
+    let graph = subgraph::flatten(root);
+    let times = graph
+        .get_nodes()
+        .iter()
+        .flat_map(|n| n.keyframes.values().flatten().map(|k| k.time));
+    let (start, end) = times.fold((f32::MAX, f32::MIN), |(start, end), t| {
+        (start.min(t), end.max(t))
+    });
+    if start > end {
+        return vec![];
+    }
+    let frames = ((end - start) * fps).round() as usize + 1;
+    (0..frames)
+        .map(|frame| {
+            let time = start + frame as f32 / fps;
+            (time, build_flat(&graph, catalog, time))
+        })
+        .collect()
+}
+
+/// What keeps the graph from running. The file itself may link or expose
+/// nodes that don't exist and use subgraphs it doesn't define, these are
+/// reported by node index in the file. With a catalog, nodes it doesn't
+/// have and links to inputs they don't have are reported by the node ids
+/// of the prompt.
+pub fn problems(root: &Graph, catalog: Option<&Catalog>) -> Vec<String> {
+    let recursive = subgraph::recursive(root.subgraphs());
+    let mut problems = structure_problems(root, root, &recursive, "");
+    for (name, subgraph) in root.subgraphs() {
+        let context = format!("subgraph {}: ", name);
+        problems.extend(structure_problems(
+            &subgraph.graph,
+            root,
+            &recursive,
+            &context,
+        ));
+        let count = subgraph.graph.get_nodes().len();
+        for port in subgraph.inputs.iter().chain(&subgraph.outputs) {
+            if port.node >= count {
+                problems.push(format!(
+                    "{}port {} is on node {}, which doesn't exist",
+                    context, port.name, port.node
+                ));
+            }
+        }
+    }
+
+    let graph = subgraph::flatten(root);
+    let mut runnable = false;
+    for (idx, node) in graph.get_nodes().iter().enumerate() {
+        if !is_executed(node) {
+            continue;
+        }
+        runnable = true;
+        let Some(catalog) = catalog else {
+            continue;
+        };
+        let Some(available) = catalog.get(&node.ty) else {
+            problems.push(format!("node {} has unknown type {}", idx, node.ty.0));
+            continue;
+        };
+        for link in graph.get_links().iter().filter(|l| l.dst_node == idx) {
+            if link.dst_slot >= available.inputs.len() {
+                problems.push(format!(
+                    "node {} ({}) has no input {}",
+                    idx, node.ty.0, link.dst_slot
+                ));
+            }
+        }
+    }
+    if !runnable {
+        problems.push("the graph has no node to run".into());
+    }
+    problems
+}
+
+/// Links to nodes `graph` doesn't have, subgraph nodes `root` has no
+/// definition for and those of the `recursive` definitions, each prefixed
+/// with `context`.
+fn structure_problems(
+    graph: &Graph,
+    root: &Graph,
+    recursive: &BTreeSet<String>,
+    context: &str,
+) -> Vec<String> {
+    let count = graph.get_nodes().len();
+    let mut problems = vec![];
+    for (idx, link) in graph.get_links().iter().enumerate() {
+        for node in [link.src_node, link.dst_node] {
+            if node >= count {
+                problems.push(format!(
+                    "{}link {} is attached to node {}, which doesn't exist",
+                    context, idx, node
+                ));
+            }
+        }
+    }
+    for (idx, node) in graph.get_nodes().iter().enumerate() {
+        let Some(name) = node.ty.subgraph_name() else {
+            continue;
+        };
+        if root.subgraph(name).is_none() {
+            problems.push(format!(
+                "{}node {} uses subgraph {}, which isn't defined",
+                context, idx, name
+            ));
+        } else if recursive.contains(name) {
+            problems.push(format!(
+                "{}node {} uses subgraph {}, which contains itself",
+                context, idx, name
+            ));
+        }
+    }
+    problems
+}
+
+fn build_flat(graph: &Graph, catalog: &Catalog, time: f32) -> Value {
+    let mut prompt = Map::new();
+    for (idx, node) in graph.get_nodes().iter().enumerate() {
+        if !is_executed(node) {
+            continue;
+        }
+        let available = catalog.get(&node.ty);
+        let mut inputs = available.map_or_else(Map::new, |n| widget_values(node, n));
+        for (widget, keys) in &node.keyframes {
+            if let Some(value) = keyframe::value_at(keys, time) {
+                inputs.insert(widget.clone(), value);
+            }
+        }
+        if let Some(available) = available {
+            for link in graph.get_links().iter().filter(|l| l.dst_node == idx) {
+                let (Some((name, _)), Some((src_node, src_slot))) = (
+                    available.inputs.get(link.dst_slot),
+                    resolve_source(graph, link),
+                ) else {
+                    continue;
+                };
+                inputs.insert(name.clone(), json!([src_node.to_string(), src_slot]));
+            }
+        }
+        prompt.insert(
+            idx.to_string(),
+            json!({ "class_type": node.ty.0, "inputs": inputs }),
+        );
+    }
+    Value::Object(prompt)
+}
+
+/// The value of each widget of `node` by name, widgets it has no value for
+/// are set to their default.
+fn widget_values(node: &NodeInstance, available: &catalog::Node) -> Map<String, Value> {
+    let list = node.widgets.as_array();
+    let mut pos = 0;
+    let mut values = Map::new();
+    for widget in &available.widgets {
+        let value = match node.widgets.as_object() {
+            Some(by_name) => by_name.get(&widget.name),
+            None => list.and_then(|l| l.get(pos)),
+        };
+        pos += 1 + widget.extra_values;
+        values.insert(
+            widget.name.clone(),
+            value.unwrap_or(&widget.default).clone(),
+        );
+    }
+    values
+}
+
+/// Notes, reroutes and inactive nodes are left out of the prompt.
+fn is_executed(node: &NodeInstance) -> bool {
+    !node.ty.is_note() && !node.ty.is_reroute() && node.mode.is_active()
+}
+
+/// Follows `link` back through reroutes and bypassed nodes to the output
+/// actually feeding it, if any. Bypassed nodes pass on their first input of
+/// the link's type, muted ones feed nothing.
+fn resolve_source(graph: &Graph, link: &Link) -> Option<(usize, usize)> {
+    let mut source = (link.src_node, link.src_slot);
+    // bounded so a loop of reroutes can't hang
+    for _ in 0..=graph.get_nodes().len() {
+        let node = graph.get_node(source.0)?;
+        let mut inputs = graph.get_links().iter().filter(|l| l.dst_node == source.0);
+        let input = match node.mode {
+            _ if node.ty.is_reroute() => inputs.next()?,
+            NodeMode::Active => return Some(source),
+            NodeMode::Muted => return None,
+            NodeMode::Bypassed => inputs
+                .filter(|l| link.ty.accepts(&l.ty))
+                .min_by_key(|l| l.dst_slot)?,
+        };
+        source = (input.src_node, input.src_slot);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LinkType, NodeType};
+
+    fn link(src_node: usize, dst_node: usize, dst_slot: usize, ty: &str) -> Link {
+        Link {
+            src_node,
+            src_slot: 0,
+            dst_node,
+            dst_slot,
+            ty: LinkType::from(ty),
+        }
+    }
+
+    /// Load -> Reroute -> Blur -> Save, with Blur also taking a mask it
+    /// lists first.
+    fn graph() -> Graph {
+        let mut graph = Graph::new();
+        for ty in ["LoadImage", "Reroute", "Blur", "SaveImage", "LoadMask"] {
+            graph.add_node_at(NodeType::from(ty), (0., 0.));
+        }
+        graph.add_link(link(0, 1, 0, "IMAGE"));
+        graph.add_link(link(4, 2, 0, "MASK"));
+        graph.add_link(link(1, 2, 1, "IMAGE"));
+        graph.add_link(link(2, 3, 0, "IMAGE"));
+        graph
+    }
+
+    #[test]
+    fn resolves_through_reroutes() {
+        let graph = graph();
+        assert_eq!(resolve_source(&graph, &graph.get_links()[2]), Some((0, 0)));
+        assert_eq!(resolve_source(&graph, &graph.get_links()[3]), Some((2, 0)));
+    }
+
+    #[test]
+    fn bypassed_nodes_pass_on_the_input_of_the_same_type() {
+        let mut graph = graph();
+        graph.set_node_mode(2, NodeMode::Bypassed);
+        assert_eq!(resolve_source(&graph, &graph.get_links()[3]), Some((0, 0)));
+    }
+
+    #[test]
+    fn muted_nodes_feed_nothing() {
+        let mut graph = graph();
+        graph.set_node_mode(2, NodeMode::Bypassed);
+        graph.set_node_mode(0, NodeMode::Muted);
+        assert_eq!(resolve_source(&graph, &graph.get_links()[3]), None);
+    }
+
+    #[test]
+    fn reroute_loops_resolve_to_nothing() {
+        let mut graph = Graph::new();
+        graph.add_node_at(NodeType::reroute(), (0., 0.));
+        graph.add_node_at(NodeType::reroute(), (0., 0.));
+        graph.add_link(link(0, 1, 0, "*"));
+        graph.add_link(link(1, 0, 0, "*"));
+        assert_eq!(resolve_source(&graph, &graph.get_links()[0]), None);
+    }
+
+    #[test]
+    fn reports_recursive_subgraphs() {
+        let mut inner = Graph::new();
+        inner.add_node_at(NodeType::subgraph("loop"), (0., 0.));
+        let mut root = graph();
+        root.add_subgraph(
+            "loop".into(),
+            crate::Subgraph {
+                graph: inner,
+                inputs: vec![],
+                outputs: vec![],
+            },
+        );
+        root.add_node_at(NodeType::subgraph("loop"), (0., 0.));
+        assert_eq!(
+            problems(&root, None),
+            [
+                "node 5 uses subgraph loop, which contains itself",
+                "subgraph loop: node 0 uses subgraph loop, which contains itself",
+            ]
+        );
+    }
+}
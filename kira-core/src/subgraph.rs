@@ -0,0 +1,456 @@
+//! Moving nodes in and out of subgraphs.
+
+use super::graph::{Graph, Link, NodeInstance, NodeMode, NodeType, Subgraph};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Links entering the selection with the inner index of their destination.
+pub type Incoming = Vec<(Link, usize)>;
+/// Outputs leaving the selection as `(node, slot)`, with the inner index of
+/// the node and every link starting there.
+pub type Outgoing = Vec<((usize, usize), (usize, Vec<Link>))>;
+
+/// Copies the `selected` nodes of `graph` and the links between them into a
+/// new graph, and collects the links crossing the selection boundary.
+pub fn extract(graph: &Graph, selected: &BTreeSet<usize>) -> (Graph, Incoming, Outgoing) {
+    let mut inner = Graph::new();
+    let mut indices = BTreeMap::new();
+    for &idx in selected {
+        let inner_idx = inner.add_node(graph.get_nodes()[idx].clone());
+        indices.insert(idx, inner_idx);
+    }
+    let mut incoming = vec![];
+    let mut outgoing = BTreeMap::<_, (usize, Vec<Link>)>::new();
+    for link in graph.get_links() {
+        match (indices.get(&link.src_node), indices.get(&link.dst_node)) {
+            (Some(&src_node), Some(&dst_node)) => inner.add_link(Link {
+                src_node,
+                dst_node,
+                ..link.clone()
+            }),
+            (None, Some(&dst)) => incoming.push((link.clone(), dst)),
+            (Some(&src), None) => outgoing
+                .entry((link.src_node, link.src_slot))
+                .or_insert_with(|| (src, vec![]))
+                .1
+                .push(link.clone()),
+            (None, None) => {}
+        }
+    }
+    (inner, incoming, outgoing.into_iter().collect())
+}
+
+/// Where the slots of a node of the original graph ended up.
+enum Ports {
+    Node(usize),
+    Expanded {
+        inputs: Vec<Option<(usize, usize)>>,
+        outputs: Vec<Option<(usize, usize)>>,
+    },
+}
+
+impl Ports {
+    fn input(&self, slot: usize) -> Option<(usize, usize)> {
+        match self {
+            Ports::Node(idx) => Some((*idx, slot)),
+            Ports::Expanded { inputs, .. } => inputs.get(slot).copied().flatten(),
+        }
+    }
+    fn output(&self, slot: usize) -> Option<(usize, usize)> {
+        match self {
+            Ports::Node(idx) => Some((*idx, slot)),
+            Ports::Expanded { outputs, .. } => outputs.get(slot).copied().flatten(),
+        }
+    }
+}
+
+/// Replaces every subgraph node of `root` by its contents, recursively.
+/// Muting or bypassing a subgraph node applies to all of its contents.
+/// Subgraphs containing themselves would never finish expanding, their
+/// nodes are left out along with their links, see [`recursive`].
+pub fn flatten(root: &Graph) -> Graph {
+    let mut flat = Graph::new();
+    let definitions = Definitions::of(root);
+    flatten_into(root, &definitions, &mut flat, (0., 0.), NodeMode::Active);
+    flat
+}
+
+fn flatten_into(
+    graph: &Graph,
+    definitions: &Definitions,
+    flat: &mut Graph,
+    origin: (f32, f32),
+    mode: NodeMode,
+) -> Vec<Ports> {
+    let ports = graph
+        .get_nodes()
+        .iter()
+        .map(|node| {
+            let pos = (origin.0 + node.pos.0, origin.1 + node.pos.1);
+            let mode = if node.mode.is_active() {
+                mode
+            } else {
+                node.mode
+            };
+            match definitions.expansion(node) {
+                Expansion::Contents(subgraph) => {
+                    let inner = flatten_into(&subgraph.graph, definitions, flat, pos, mode);
+                    let resolve = |ports: &[super::graph::Port], output: bool| {
+                        ports
+                            .iter()
+                            .map(|p| {
+                                let ports = inner.get(p.node)?;
+                                if output {
+                                    ports.output(p.slot)
+                                } else {
+                                    ports.input(p.slot)
+                                }
+                            })
+                            .collect()
+                    };
+                    Ports::Expanded {
+                        inputs: resolve(&subgraph.inputs, false),
+                        outputs: resolve(&subgraph.outputs, true),
+                    }
+                }
+                Expansion::Nothing => Ports::Expanded {
+                    inputs: vec![],
+                    outputs: vec![],
+                },
+                Expansion::Node => {
+                    let idx = flat.add_node(NodeInstance {
+                        pos,
+                        mode,
+                        ..node.clone()
+                    });
+                    Ports::Node(idx)
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    for link in graph.get_links() {
+        let src = ports
+            .get(link.src_node)
+            .and_then(|p| p.output(link.src_slot));
+        let dst = ports
+            .get(link.dst_node)
+            .and_then(|p| p.input(link.dst_slot));
+        if let (Some((src_node, src_slot)), Some((dst_node, dst_slot))) = (src, dst) {
+            flat.add_link(Link {
+                src_node,
+                src_slot,
+                dst_node,
+                dst_slot,
+                ty: link.ty.clone(),
+            });
+        }
+    }
+    ports
+}
+
+/// The index each node of `root` has in the flattened graph, subgraph
+/// nodes are expanded into their contents and have none.
+pub fn flat_indices(root: &Graph) -> Vec<Option<usize>> {
+    let definitions = Definitions::of(root);
+    let mut next = 0;
+    root.get_nodes()
+        .iter()
+        .map(|node| match definitions.expansion(node) {
+            Expansion::Contents(subgraph) => {
+                next += flat_len(&subgraph.graph, &definitions);
+                None
+            }
+            Expansion::Nothing => None,
+            Expansion::Node => {
+                next += 1;
+                Some(next - 1)
+            }
+        })
+        .collect()
+}
+
+/// How many nodes `graph` flattens into.
+fn flat_len(graph: &Graph, definitions: &Definitions) -> usize {
+    graph
+        .get_nodes()
+        .iter()
+        .map(|node| match definitions.expansion(node) {
+            Expansion::Contents(subgraph) => flat_len(&subgraph.graph, definitions),
+            Expansion::Nothing => 0,
+            Expansion::Node => 1,
+        })
+        .sum()
+}
+
+/// The names of the definitions that contain themselves, directly or
+/// through other definitions.
+pub fn recursive(definitions: &BTreeMap<String, Subgraph>) -> BTreeSet<String> {
+    definitions
+        .iter()
+        .filter(|(name, subgraph)| used(&subgraph.graph, definitions).contains_key(*name))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// The definitions of a root graph, with the recursive ones found once.
+struct Definitions<'a> {
+    all: &'a BTreeMap<String, Subgraph>,
+    recursive: BTreeSet<String>,
+}
+
+/// What a node turns into when flattening.
+enum Expansion<'a> {
+    Node,
+    Contents(&'a Subgraph),
+    /// Nodes of recursive subgraphs, left out.
+    Nothing,
+}
+
+impl<'a> Definitions<'a> {
+    fn of(root: &'a Graph) -> Self {
+        Definitions {
+            all: root.subgraphs(),
+            recursive: recursive(root.subgraphs()),
+        }
+    }
+    fn expansion(&self, node: &NodeInstance) -> Expansion<'a> {
+        let Some(name) = node.ty.subgraph_name() else {
+            return Expansion::Node;
+        };
+        if self.recursive.contains(name) {
+            return Expansion::Nothing;
+        }
+        match self.all.get(name) {
+            Some(subgraph) => Expansion::Contents(subgraph),
+            None => Expansion::Node,
+        }
+    }
+}
+
+/// New names for the `incoming` definitions that would otherwise be
+/// mistaken for different definitions of the same name in `existing`, as
+/// `Name 2` and so on. Identical definitions are shared.
+pub fn renames(
+    existing: &BTreeMap<String, Subgraph>,
+    incoming: &BTreeMap<String, Subgraph>,
+) -> BTreeMap<String, String> {
+    let mut renames = BTreeMap::<String, String>::new();
+    // a definition using a renamed one no longer matches its namesake
+    // either, so this goes on until nothing else clashes
+    loop {
+        let clash = incoming.iter().find(|(name, definition)| {
+            !renames.contains_key(*name)
+                && existing.get(*name).is_some_and(|other| {
+                    !same(other, definition)
+                        || definition.graph.get_nodes().iter().any(|n| {
+                            n.ty.subgraph_name()
+                                .is_some_and(|n| renames.contains_key(n))
+                        })
+                })
+        });
+        let Some((name, _)) = clash else {
+            return renames;
+        };
+        let taken = |new: &str| {
+            existing.contains_key(new)
+                || incoming.contains_key(new)
+                || renames.values().any(|r| r == new)
+        };
+        let mut n = 2;
+        while taken(&format!("{} {}", name, n)) {
+            n += 1;
+        }
+        renames.insert(name.clone(), format!("{} {}", name, n));
+    }
+}
+
+fn same(a: &Subgraph, b: &Subgraph) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Points the subgraph nodes of `graph` at the new names of `renames`.
+pub fn retype(graph: &mut Graph, renames: &BTreeMap<String, String>) {
+    for idx in 0..graph.get_nodes().len() {
+        let renamed = graph.get_nodes()[idx]
+            .ty
+            .subgraph_name()
+            .and_then(|name| renames.get(name));
+        if let Some(name) = renamed {
+            graph.set_node_type(idx, NodeType::subgraph(name));
+        }
+    }
+}
+
+/// The definitions `graph` relies on, including the ones nested in them.
+pub fn used(graph: &Graph, definitions: &BTreeMap<String, Subgraph>) -> BTreeMap<String, Subgraph> {
+    let mut used = BTreeMap::new();
+    let mut pending = vec![graph];
+    while let Some(graph) = pending.pop() {
+        for node in graph.get_nodes() {
+            let Some(name) = node.ty.subgraph_name() else {
+                continue;
+            };
+            if used.contains_key(name) {
+                continue;
+            }
+            if let Some(subgraph) = definitions.get(name) {
+                used.insert(name.to_owned(), subgraph.clone());
+                pending.push(&subgraph.graph);
+            }
+        }
+    }
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{LinkType, NodeType, Port};
+
+    fn port(name: &str) -> Port {
+        Port {
+            name: name.into(),
+            ty: LinkType::from("IMAGE"),
+            node: 0,
+            slot: 0,
+        }
+    }
+
+    fn link(src_node: usize, dst_node: usize) -> Link {
+        Link {
+            src_node,
+            src_slot: 0,
+            dst_node,
+            dst_slot: 0,
+            ty: LinkType::from("IMAGE"),
+        }
+    }
+
+    /// Load -> blur -> Save, where blur is a subgraph holding a single
+    /// Blur node.
+    fn graph() -> Graph {
+        let mut inner = Graph::new();
+        inner.add_node_at(NodeType::from("Blur"), (10., 20.));
+        let mut root = Graph::new();
+        root.add_subgraph(
+            "blur".into(),
+            Subgraph {
+                graph: inner,
+                inputs: vec![port("image")],
+                outputs: vec![port("IMAGE")],
+            },
+        );
+        root.add_node_at(NodeType::from("LoadImage"), (0., 0.));
+        root.add_node_at(NodeType::subgraph("blur"), (100., 100.));
+        root.add_node_at(NodeType::from("SaveImage"), (200., 0.));
+        root.add_link(link(0, 1));
+        root.add_link(link(1, 2));
+        root
+    }
+
+    #[test]
+    fn expands_subgraph_nodes() {
+        let flat = flatten(&graph());
+        let types = flat
+            .get_nodes()
+            .iter()
+            .map(|n| n.ty.0.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(types, ["LoadImage", "Blur", "SaveImage"]);
+        assert_eq!(flat.get_nodes()[1].pos, (110., 120.));
+        let links = flat
+            .get_links()
+            .iter()
+            .map(|l| (l.src_node, l.dst_node))
+            .collect::<Vec<_>>();
+        assert_eq!(links, [(0, 1), (1, 2)]);
+        assert_eq!(flat_indices(&graph()), [Some(0), None, Some(2)]);
+    }
+
+    #[test]
+    fn subgraph_modes_apply_to_their_contents() {
+        let mut root = graph();
+        root.set_node_mode(1, NodeMode::Bypassed);
+        assert_eq!(flatten(&root).get_nodes()[1].mode, NodeMode::Bypassed);
+    }
+
+    fn definition(ty: NodeType) -> Subgraph {
+        let mut graph = Graph::new();
+        graph.add_node_at(ty, (0., 0.));
+        Subgraph {
+            graph,
+            inputs: vec![port("image")],
+            outputs: vec![port("IMAGE")],
+        }
+    }
+
+    #[test]
+    fn renames_clashing_definitions() {
+        let existing = BTreeMap::from([
+            ("blur".to_owned(), definition(NodeType::from("Blur"))),
+            ("blur 2".to_owned(), definition(NodeType::from("Blur"))),
+            ("outer".to_owned(), definition(NodeType::subgraph("blur"))),
+            ("same".to_owned(), definition(NodeType::from("Blur"))),
+        ]);
+        let incoming = BTreeMap::from([
+            ("blur".to_owned(), definition(NodeType::from("Sharpen"))),
+            ("outer".to_owned(), definition(NodeType::subgraph("blur"))),
+            ("same".to_owned(), definition(NodeType::from("Blur"))),
+        ]);
+        let renames = renames(&existing, &incoming);
+        assert_eq!(
+            renames,
+            BTreeMap::from([
+                ("blur".to_owned(), "blur 3".to_owned()),
+                ("outer".to_owned(), "outer 2".to_owned()),
+            ])
+        );
+
+        let mut outer = incoming["outer"].graph.clone();
+        retype(&mut outer, &renames);
+        assert_eq!(outer.get_nodes()[0].ty, NodeType::subgraph("blur 3"));
+    }
+
+    #[test]
+    fn leaves_out_recursive_subgraphs() {
+        // loop uses itself through other, which blur uses too
+        let mut root = graph();
+        let mut definitions = BTreeMap::new();
+        for (name, inner) in [("loop", "other"), ("other", "loop")] {
+            let mut graph = Graph::new();
+            for _ in 0..3 {
+                graph.add_node_at(NodeType::subgraph(inner), (0., 0.));
+            }
+            definitions.insert(name.to_owned(), graph);
+        }
+        for (name, graph) in definitions {
+            root.add_subgraph(
+                name,
+                Subgraph {
+                    graph,
+                    inputs: vec![],
+                    outputs: vec![],
+                },
+            );
+        }
+        root.subgraph_mut("blur")
+            .unwrap()
+            .graph
+            .add_node_at(NodeType::subgraph("other"), (0., 0.));
+        root.add_node_at(NodeType::subgraph("loop"), (0., 0.));
+        root.add_link(link(3, 2));
+
+        assert_eq!(
+            recursive(root.subgraphs()),
+            BTreeSet::from(["loop".to_owned(), "other".to_owned()])
+        );
+        let flat = flatten(&root);
+        let types = flat
+            .get_nodes()
+            .iter()
+            .map(|n| n.ty.0.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(types, ["LoadImage", "Blur", "SaveImage"]);
+        assert_eq!(flat.get_links().len(), 2);
+        assert_eq!(flat_indices(&root), [Some(0), None, Some(2), None]);
+    }
+}
@@ -0,0 +1,465 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Segments can't be trimmed shorter than this, in seconds.
+const MIN_DURATION: f32 = 0.1;
+/// Length of a segment added without a duration, in seconds.
+const DEFAULT_DURATION: f32 = 2.;
+const DEFAULT_FPS: f32 = 24.;
+const MAX_FPS: f32 = 120.;
+/// How close a moved segment edge has to be to another segment's edge to
+/// snap to it, in seconds.
+const SNAP_DISTANCE: f32 = 0.1;
+
+/// The clips the project is edited into, times are in seconds.
+///
+/// Segments sit on tracks, and the first track is the topmost one: where
+/// segments overlap in time, the one on the earlier track is shown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timeline {
+    #[serde(default = "default_tracks")]
+    tracks: Vec<Track>,
+    segments: Vec<Segment>,
+    #[serde(default = "default_fps", deserialize_with = "deserialize_fps")]
+    fps: f32,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            tracks: default_tracks(),
+            segments: vec![],
+            fps: DEFAULT_FPS,
+        }
+    }
+}
+
+fn default_tracks() -> Vec<Track> {
+    vec![Track::new("Track 1".into())]
+}
+
+fn default_fps() -> f32 {
+    DEFAULT_FPS
+}
+
+/// Files are edited by hand too, a frame rate below one would have the
+/// timeline count frames without end.
+fn deserialize_fps<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    f32::deserialize(deserializer).map(clamp_fps)
+}
+
+fn clamp_fps(fps: f32) -> f32 {
+    fps.clamp(1., MAX_FPS)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Track {
+    pub name: String,
+    /// Muted tracks are left out of playback.
+    #[serde(default)]
+    pub muted: bool,
+    /// The segments of locked tracks can't be edited.
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl Track {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            muted: false,
+            locked: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    /// Index of the track the segment is on.
+    #[serde(default)]
+    pub track: usize,
+    pub start: f32,
+    pub duration: f32,
+    pub title: String,
+    /// Paths of the images the segment shows, a single one for a still or
+    /// one per frame for a sequence.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<String>,
+}
+
+impl Segment {
+    /// A segment of the default length.
+    pub fn new(track: usize, start: f32, title: String, frames: Vec<String>) -> Self {
+        Self {
+            track,
+            start,
+            duration: DEFAULT_DURATION,
+            title,
+            frames,
+        }
+    }
+    /// The image standing for the segment on the timeline.
+    pub fn preview(&self) -> Option<&str> {
+        self.frames.first().map(String::as_str)
+    }
+}
+
+impl Timeline {
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+    /// Whether there is nothing worth saving.
+    pub fn is_default(&self) -> bool {
+        self.is_empty() && self.fps == DEFAULT_FPS && self.tracks == default_tracks()
+    }
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+    pub fn set_fps(&mut self, fps: f32) {
+        self.fps = clamp_fps(fps);
+    }
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+    pub fn add_track(&mut self) -> usize {
+        let name = format!("Track {}", self.tracks.len() + 1);
+        self.tracks.push(Track::new(name));
+        self.tracks.len() - 1
+    }
+    /// Removes a track along with its segments, the last track is kept.
+    pub fn remove_track(&mut self, idx: usize) {
+        if idx >= self.tracks.len() || self.tracks.len() == 1 {
+            return;
+        }
+        self.tracks.remove(idx);
+        self.segments.retain(|s| s.track != idx);
+        for segment in &mut self.segments {
+            if segment.track > idx {
+                segment.track -= 1;
+            }
+        }
+    }
+    /// Moves track `from` to position `to`, taking its segments along.
+    pub fn move_track(&mut self, from: usize, to: usize) {
+        if from >= self.tracks.len() || to >= self.tracks.len() {
+            return;
+        }
+        // previous index of the track at each position
+        let mut order = (0..self.tracks.len()).collect::<Vec<_>>();
+        order.remove(from);
+        order.insert(to, from);
+        let track = self.tracks.remove(from);
+        self.tracks.insert(to, track);
+        for segment in &mut self.segments {
+            segment.track = order.iter().position(|&i| i == segment.track).unwrap_or(0);
+        }
+    }
+    pub fn rename_track(&mut self, idx: usize, name: String) {
+        if let Some(track) = self.tracks.get_mut(idx) {
+            track.name = name;
+        }
+    }
+    pub fn toggle_track_mute(&mut self, idx: usize) {
+        if let Some(track) = self.tracks.get_mut(idx) {
+            track.muted = !track.muted;
+        }
+    }
+    pub fn toggle_track_lock(&mut self, idx: usize) {
+        if let Some(track) = self.tracks.get_mut(idx) {
+            track.locked = !track.locked;
+        }
+    }
+    /// Missing tracks count as locked, as nothing can go there.
+    fn is_locked(&self, track: usize) -> bool {
+        match self.tracks.get(track) {
+            Some(track) => track.locked,
+            None => true,
+        }
+    }
+    /// Whether segment `idx` exists and its track isn't locked.
+    fn is_editable(&self, idx: usize) -> bool {
+        self.segments
+            .get(idx)
+            .is_some_and(|s| !self.is_locked(s.track))
+    }
+    /// Number of frames until the end of the last segment.
+    pub fn frame_count(&self) -> usize {
+        (self.end() * self.fps).ceil() as usize
+    }
+    /// The image shown at frame `frame`, from the topmost segment there that
+    /// isn't muted. Sequences play at the timeline's frame rate and hold
+    /// their last image, stills are shown for the whole segment.
+    pub fn image_at(&self, frame: usize) -> Option<&str> {
+        self.image_at_time(frame as f32 / self.fps)
+    }
+    /// The image shown `t` seconds in, like [`Timeline::image_at`].
+    pub fn image_at_time(&self, t: f32) -> Option<&str> {
+        // on the same track the segment added last is on top
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .filter(|s| self.tracks.get(s.track).is_some_and(|t| !t.muted))
+            .filter(|s| s.start <= t && t < s.start + s.duration)
+            .min_by_key(|s| s.track)?;
+        let idx = ((t - segment.start) * self.fps).floor() as usize;
+        segment
+            .frames
+            .get(idx.min(segment.frames.len().saturating_sub(1)))
+            .map(String::as_str)
+    }
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+    /// Where the last segment ends.
+    pub fn end(&self) -> f32 {
+        self.segments
+            .iter()
+            .map(|s| s.start + s.duration)
+            .fold(0., f32::max)
+    }
+    /// Adds `segment` unless its track is locked, a missing track stands for
+    /// the last one.
+    pub fn add_segment(&mut self, segment: Segment) -> Option<usize> {
+        let track = segment.track.min(self.tracks.len().saturating_sub(1));
+        if self.is_locked(track) {
+            return None;
+        }
+        self.segments.push(Segment {
+            track,
+            start: segment.start.max(0.),
+            duration: segment.duration.max(MIN_DURATION),
+            ..segment
+        });
+        Some(self.segments.len() - 1)
+    }
+    /// Moves a segment, and onto `track` unless that one is locked. Either
+    /// of its edges snaps to the edges of the other segments when close
+    /// enough, and its start to a frame otherwise.
+    pub fn move_segment(&mut self, idx: usize, start: f32, track: usize) {
+        if !self.is_editable(idx) {
+            return;
+        }
+        let duration = self.segments[idx].duration;
+        let edges = self.edges(idx);
+        let start = nearest(start, &edges)
+            .or_else(|| nearest(start + duration, &edges).map(|end| end - duration))
+            .unwrap_or_else(|| self.to_frame(start));
+        let track = if self.is_locked(track) {
+            self.segments[idx].track
+        } else {
+            track
+        };
+        let segment = &mut self.segments[idx];
+        segment.start = start.max(0.);
+        segment.track = track;
+    }
+    /// Sets both ends of a segment at once, as trimming its start also
+    /// changes its duration. Each end snaps like when moving.
+    pub fn trim_segment(&mut self, idx: usize, start: f32, duration: f32) {
+        if !self.is_editable(idx) {
+            return;
+        }
+        let edges = self.edges(idx);
+        let snap = |t: f32| nearest(t, &edges).unwrap_or_else(|| self.to_frame(t));
+        let (start, end) = (snap(start), snap(start + duration));
+        let segment = &mut self.segments[idx];
+        segment.start = start.max(0.).min(end - MIN_DURATION).max(0.);
+        segment.duration = (end - segment.start).max(MIN_DURATION);
+    }
+    /// Starts and ends of the segments other than `idx`.
+    fn edges(&self, idx: usize) -> Vec<f32> {
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != idx)
+            .flat_map(|(_, s)| [s.start, s.start + s.duration])
+            .collect()
+    }
+    fn to_frame(&self, t: f32) -> f32 {
+        (t * self.fps).round() / self.fps
+    }
+    pub fn remove_segment(&mut self, idx: usize) {
+        if self.is_editable(idx) {
+            self.segments.remove(idx);
+        }
+    }
+}
+
+/// The closest of `edges` within snapping distance of `t`.
+fn nearest(t: f32, edges: &[f32]) -> Option<f32> {
+    edges
+        .iter()
+        .copied()
+        .filter(|e| (e - t).abs() < SNAP_DISTANCE)
+        .min_by(|a, b| (a - t).abs().total_cmp(&(b - t).abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn clamps_the_frame_rate_of_files() {
+        for (fps, clamped) in [(0., 1.), (-24., 1.), (1000., MAX_FPS), (12., 12.)] {
+            let timeline: Timeline =
+                serde_json::from_value(json!({ "segments": [], "fps": fps })).unwrap();
+            assert_eq!(timeline.fps(), clamped);
+        }
+        let timeline: Timeline = serde_json::from_value(json!({ "segments": [] })).unwrap();
+        assert_eq!(timeline.fps(), DEFAULT_FPS);
+    }
+
+    fn still(track: usize, start: f32, path: &str) -> Segment {
+        Segment::new(track, start, path.into(), vec![path.into()])
+    }
+
+    #[test]
+    fn round_trips_through_files() {
+        let mut timeline = Timeline::default();
+        timeline.add_track();
+        timeline.rename_track(1, "Overlay".into());
+        timeline.toggle_track_mute(1);
+        timeline.set_fps(12.);
+        timeline.add_segment(still(0, 0., "a.png"));
+        timeline.add_segment(Segment::new(
+            1,
+            1.,
+            "Render".into(),
+            vec!["b.png".into(), "c.png".into()],
+        ));
+        let json = serde_json::to_value(&timeline).unwrap();
+        assert_eq!(serde_json::from_value::<Timeline>(json).unwrap(), timeline);
+    }
+
+    #[test]
+    fn reads_files_from_before_tracks() {
+        let timeline: Timeline = serde_json::from_value(json!({
+            "segments": [{ "start": 1, "duration": 2, "title": "a" }],
+        }))
+        .unwrap();
+        assert_eq!(timeline.tracks(), default_tracks());
+        assert_eq!(timeline.fps(), DEFAULT_FPS);
+        assert_eq!(timeline.segments()[0].track, 0);
+        assert!(timeline.segments()[0].frames.is_empty());
+    }
+
+    #[test]
+    fn trims_to_edges_and_frames() {
+        let mut timeline = Timeline::default();
+        timeline.add_segment(Segment {
+            duration: 2.,
+            ..still(0, 0., "a.png")
+        });
+        timeline.add_segment(Segment {
+            duration: 1.,
+            ..still(0, 5., "b.png")
+        });
+        // the start lands on the end of the other segment
+        timeline.trim_segment(1, 2.05, 3.95);
+        assert_eq!(
+            (
+                timeline.segments()[1].start,
+                timeline.segments()[1].duration
+            ),
+            (2., 4.)
+        );
+        // and on a frame away from other segments
+        timeline.trim_segment(1, 3.01, 2.99);
+        assert_eq!(timeline.segments()[1].start, 3.);
+        // an end before the start leaves the shortest segment
+        timeline.trim_segment(1, 3., -1.);
+        assert!((timeline.segments()[1].duration - MIN_DURATION).abs() < 1e-5);
+    }
+
+    /// Three tracks with a segment on each, titled after its track.
+    fn tracks() -> Timeline {
+        let mut timeline = Timeline::default();
+        timeline.add_track();
+        timeline.add_track();
+        for track in 0..3 {
+            timeline.add_segment(still(track, track as f32 * 3., &format!("{}", track)));
+        }
+        timeline
+    }
+
+    fn layout(timeline: &Timeline) -> Vec<(String, usize)> {
+        timeline
+            .segments()
+            .iter()
+            .map(|s| (s.title.clone(), s.track))
+            .collect()
+    }
+
+    fn names(timeline: &Timeline) -> Vec<&str> {
+        timeline.tracks().iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    fn reordering_tracks_takes_their_segments_along() {
+        let mut timeline = tracks();
+        timeline.move_track(0, 2);
+        assert_eq!(names(&timeline), ["Track 2", "Track 3", "Track 1"]);
+        assert_eq!(
+            layout(&timeline),
+            [("0".into(), 2), ("1".into(), 0), ("2".into(), 1)]
+        );
+        timeline.move_track(2, 0);
+        assert_eq!(names(&timeline), ["Track 1", "Track 2", "Track 3"]);
+        assert_eq!(
+            layout(&timeline),
+            [("0".into(), 0), ("1".into(), 1), ("2".into(), 2)]
+        );
+    }
+
+    #[test]
+    fn removing_a_track_removes_its_segments() {
+        let mut timeline = tracks();
+        timeline.remove_track(1);
+        assert_eq!(names(&timeline), ["Track 1", "Track 3"]);
+        assert_eq!(layout(&timeline), [("0".into(), 0), ("2".into(), 1)]);
+        timeline.remove_track(1);
+        timeline.remove_track(0);
+        assert_eq!(names(&timeline), ["Track 1"]);
+    }
+
+    #[test]
+    fn locked_tracks_are_left_alone() {
+        let mut timeline = tracks();
+        timeline.toggle_track_lock(1);
+        // moving onto a locked track only moves in time
+        timeline.move_segment(0, 0.5, 1);
+        assert_eq!(layout(&timeline)[0], ("0".into(), 0));
+        assert_eq!(timeline.segments()[0].start, 0.5);
+        // segments on a locked track stay put
+        timeline.move_segment(1, 10., 0);
+        timeline.remove_segment(1);
+        assert_eq!(layout(&timeline)[1], ("1".into(), 1));
+        assert_eq!(timeline.segments()[1].start, 3.);
+        assert_eq!(timeline.add_segment(still(1, 0., "x")), None);
+        // a missing track stands for the last one
+        assert_eq!(timeline.add_segment(still(7, 0., "x")), Some(3));
+        assert_eq!(timeline.segments()[3].track, 2);
+        timeline.toggle_track_lock(2);
+        assert_eq!(timeline.add_segment(still(7, 0., "x")), None);
+    }
+
+    #[test]
+    fn shows_the_topmost_segment() {
+        let mut timeline = Timeline::default();
+        timeline.add_track();
+        timeline.add_segment(still(1, 0., "below.png"));
+        timeline.add_segment(Segment {
+            duration: 1.,
+            ..Segment::new(0, 0.5, "above".into(), vec!["a.png".into(), "b.png".into()])
+        });
+        assert_eq!(timeline.image_at_time(0.25), Some("below.png"));
+        assert_eq!(timeline.image_at_time(0.5), Some("a.png"));
+        // sequences hold their last image
+        assert_eq!(timeline.image_at_time(1.25), Some("b.png"));
+        assert_eq!(timeline.image_at_time(1.75), Some("below.png"));
+        assert_eq!(timeline.image_at_time(2.5), None);
+        timeline.toggle_track_mute(0);
+        assert_eq!(timeline.image_at_time(0.5), Some("below.png"));
+    }
+}
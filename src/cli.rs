@@ -0,0 +1,429 @@
+//! Subcommands run from the command line without opening a window.
+
+use kira_core::{
+    catalog, prompt, read_catalog, read_project, save_project, save_workflow, subgraph, Backend,
+    Catalog, Graph, NodeType, Timeline,
+};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The command did what it was asked, the prompt ran and its outputs were
+/// saved or every file is valid.
+const EXIT_OK: i32 = 0;
+/// The prompt failed on the backend or its outputs couldn't be fetched.
+const EXIT_FAILED: i32 = 1;
+/// The command line is wrong.
+const EXIT_USAGE: i32 = 2;
+/// A file can't be read, converted or its graph can't run.
+const EXIT_INVALID: i32 = 3;
+/// The backend can't be reached.
+const EXIT_UNREACHABLE: i32 = 4;
+
+const RUN_USAGE: &str =
+    "usage: kira run <graph.kira> [--backend <url>] [--set <node>.<input>=<value>]... [--out <dir>]";
+const VALIDATE_USAGE: &str = "usage: kira validate <file>... [--catalog <object_info.json>]";
+const CONVERT_USAGE: &str = "usage: kira convert --to comfy-workflow|comfy-api|kira <file>... \
+     [--catalog <object_info.json>] [--out <dir>] [--allow-loss]";
+const INSPECT_USAGE: &str = "usage: kira inspect <file>... [--catalog <object_info.json>]";
+
+/// Runs the subcommand `args` start with and returns the exit code, or
+/// `None` when they are files for the editor to open instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, args) = args.split_first()?;
+    match command.as_str() {
+        "run" => Some(run_graph(args)),
+        "validate" => Some(validate(args)),
+        "convert" => Some(convert(args)),
+        "inspect" => Some(inspect(args)),
+        _ => None,
+    }
+}
+
+struct RunArgs {
+    path: String,
+    backend: Backend,
+    /// Node id in the prompt, input name and value.
+    overrides: Vec<(String, String, Value)>,
+    out: PathBuf,
+}
+
+fn parse_run_args(args: &[String]) -> Result<RunArgs, String> {
+    let mut path = None;
+    let mut backend = Backend::new();
+    let mut overrides = vec![];
+    let mut out = PathBuf::from(".");
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--backend" => backend = Backend::with_url(&value()?),
+            "--out" => out = value()?.into(),
+            "--set" => overrides.push(parse_override(&value()?)?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    Ok(RunArgs {
+        path: path.ok_or("missing graph file")?,
+        backend,
+        overrides,
+        out,
+    })
+}
+
+/// `3.seed=42` sets input `seed` of node 3 to 42. Values are read as JSON
+/// when they can be, and as plain text otherwise.
+fn parse_override(arg: &str) -> Result<(String, String, Value), String> {
+    let invalid = || format!("--set expects <node>.<input>=<value>, got {}", arg);
+    let (key, value) = arg.split_once('=').ok_or_else(invalid)?;
+    let (node, input) = key.split_once('.').ok_or_else(invalid)?;
+    if node.parse::<usize>().is_err() || input.is_empty() {
+        return Err(invalid());
+    }
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::from(value));
+    Ok((node.into(), input.into(), value))
+}
+
+fn run_graph(args: &[String]) -> i32 {
+    let args = match parse_run_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, RUN_USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let (graph, _) = match read_project(&args.path) {
+        Ok(project) => project,
+        Err(e) => {
+            eprintln!("Could not open {}: {}", args.path, e);
+            return EXIT_INVALID;
+        }
+    };
+    let backend = args.backend;
+    let catalog = match backend.available_nodes() {
+        Ok(catalog) => catalog,
+        Err(e) => {
+            eprintln!("Could not reach the backend: {}", e);
+            return EXIT_UNREACHABLE;
+        }
+    };
+    let problems = prompt::problems(&graph, Some(&catalog));
+    if !problems.is_empty() {
+        for problem in problems {
+            eprintln!("{}: {}", args.path, problem);
+        }
+        return EXIT_INVALID;
+    }
+
+    let mut prompt = prompt::build(&graph, &catalog, 0.);
+    for (node, input, value) in args.overrides {
+        let Some(inputs) = prompt
+            .get_mut(&node)
+            .and_then(|n| n.get_mut("inputs"))
+            .and_then(Value::as_object_mut)
+        else {
+            eprintln!(
+                "{}: there is no node {} to set {} on",
+                args.path, node, input
+            );
+            return EXIT_INVALID;
+        };
+        inputs.insert(input, value);
+    }
+
+    let id = match backend.run_prompt(&prompt) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Could not run {}: {}", args.path, e);
+            return EXIT_FAILED;
+        }
+    };
+    match backend.wait_for_outputs(&id, &args.out) {
+        Ok(outputs) => {
+            for path in outputs.values().flatten() {
+                println!("{}", path.display());
+            }
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("Could not fetch the outputs of prompt {}: {}", id, e);
+            EXIT_FAILED
+        }
+    }
+}
+
+/// Files and options of the commands working on files, the catalog is the
+/// `object_info` of a backend saved to a file.
+#[derive(Default)]
+struct FileArgs {
+    files: Vec<String>,
+    catalog: Option<Catalog>,
+    to: Option<Target>,
+    out: Option<PathBuf>,
+    allow_loss: bool,
+}
+
+/// Reads `args`, accepting only the given `options`.
+fn parse_file_args(args: &[String], options: &[&str]) -> Result<FileArgs, String> {
+    let mut parsed = FileArgs::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") && !options.contains(&arg.as_str()) {
+            return Err(format!("unknown option {}", arg));
+        }
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--catalog" => {
+                let path = value()?;
+                let catalog = read_catalog(&path)
+                    .map_err(|e| format!("could not read catalog {}: {}", path, e))?;
+                parsed.catalog = Some(catalog);
+            }
+            "--to" => parsed.to = Some(Target::parse(&value()?)?),
+            "--out" => parsed.out = Some(value()?.into()),
+            "--allow-loss" => parsed.allow_loss = true,
+            _ => parsed.files.push(arg.clone()),
+        }
+    }
+    if parsed.files.is_empty() {
+        return Err("missing file".into());
+    }
+    Ok(parsed)
+}
+
+/// Parses the command line of a file command, or prints what's wrong with
+/// it along with `usage`.
+fn file_args(args: &[String], options: &[&str], usage: &str) -> Result<FileArgs, i32> {
+    parse_file_args(args, options).map_err(|e| {
+        eprintln!("{}\n{}", e, usage);
+        EXIT_USAGE
+    })
+}
+
+/// Prints the problems of each file, see [`prompt::problems`]. Without a
+/// catalog node types and inputs aren't checked.
+fn validate(args: &[String]) -> i32 {
+    let args = match file_args(args, &["--catalog"], VALIDATE_USAGE) {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+    let mut code = EXIT_OK;
+    for path in &args.files {
+        let problems = match read_project(path) {
+            Ok((graph, _)) => prompt::problems(&graph, args.catalog.as_ref()),
+            Err(e) => vec![e.to_string()],
+        };
+        for problem in &problems {
+            println!("{}: {}", path, problem);
+        }
+        if !problems.is_empty() {
+            code = EXIT_INVALID;
+        }
+    }
+    code
+}
+
+/// The formats files can be converted to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Target {
+    ComfyWorkflow,
+    ComfyApi,
+    Kira,
+}
+
+impl Target {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "comfy-workflow" => Ok(Target::ComfyWorkflow),
+            "comfy-api" => Ok(Target::ComfyApi),
+            "kira" => Ok(Target::Kira),
+            _ => Err(format!("unknown format {}", name)),
+        }
+    }
+    /// Both ComfyUI formats are JSON, API prompts get a suffix so they don't
+    /// overwrite the workflow they come from.
+    fn extension(self) -> &'static str {
+        match self {
+            Target::ComfyWorkflow => "json",
+            Target::ComfyApi => "api.json",
+            Target::Kira => "kira",
+        }
+    }
+}
+
+/// Converts each file next to it, or into `--out`. ComfyUI formats have no
+/// subgraphs, keyframes nor timeline, files using them are only converted
+/// with `--allow-loss`, which flattens the graph and drops the rest. API
+/// prompts name the inputs of their nodes so they need a catalog that has
+/// every node of the graph.
+fn convert(args: &[String]) -> i32 {
+    let options = ["--to", "--catalog", "--out", "--allow-loss"];
+    let args = match file_args(args, &options, CONVERT_USAGE) {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+    let Some(target) = args.to else {
+        eprintln!("missing --to\n{}", CONVERT_USAGE);
+        return EXIT_USAGE;
+    };
+    if target == Target::ComfyApi && args.catalog.is_none() {
+        eprintln!("comfy-api needs --catalog\n{}", CONVERT_USAGE);
+        return EXIT_USAGE;
+    }
+    let mut nodes = args.catalog.unwrap_or_default();
+    nodes.extend(catalog::builtin_nodes());
+
+    let mut code = EXIT_OK;
+    for path in &args.files {
+        match convert_file(path, target, &nodes, args.out.as_deref(), args.allow_loss) {
+            Ok(converted) => println!("{}", converted.display()),
+            Err(e) => {
+                eprintln!("Could not convert {}: {}", path, e);
+                code = EXIT_INVALID;
+            }
+        }
+    }
+    code
+}
+
+fn convert_file(
+    path: &str,
+    target: Target,
+    nodes: &Catalog,
+    out: Option<&Path>,
+    allow_loss: bool,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let input = Path::new(path);
+    let stem = input.file_stem().ok_or("not a file")?.to_string_lossy();
+    let dir = out.or_else(|| input.parent()).unwrap_or(Path::new(""));
+    let converted = dir.join(format!("{}.{}", stem, target.extension()));
+    if converted == input {
+        return Err("it is already in that format, use --out to copy it".into());
+    }
+
+    let (graph, timeline) = read_project(path)?;
+    let lost = losses(&graph, &timeline, target);
+    if !lost.is_empty() && !allow_loss {
+        let lost = lost.join(", ");
+        return Err(format!("{} would be lost, use --allow-loss to convert anyway", lost).into());
+    }
+    if target == Target::ComfyApi {
+        let problems = prompt::problems(&graph, Some(nodes));
+        if !problems.is_empty() {
+            return Err(problems.join(", ").into());
+        }
+    }
+    if let Some(dir) = out {
+        fs::create_dir_all(dir)?;
+    }
+    let converted_str = converted.to_str().ok_or("invalid output path")?;
+    match target {
+        Target::Kira => save_project(converted_str, &graph, &timeline)?,
+        Target::ComfyWorkflow => {
+            // nodes keep the size they had in ComfyUI, or let it work one out
+            save_workflow(converted_str, &subgraph::flatten(&graph), nodes, &[])?
+        }
+        Target::ComfyApi => {
+            let prompt = prompt::build(&graph, nodes, 0.);
+            fs::write(&converted, serde_json::to_string(&prompt)?)?
+        }
+    }
+    Ok(converted)
+}
+
+/// What of the project converting it to `target` would drop.
+fn losses(graph: &Graph, timeline: &Timeline, target: Target) -> Vec<&'static str> {
+    if target == Target::Kira {
+        return vec![];
+    }
+    let mut lost = vec![];
+    if !graph.subgraphs().is_empty() {
+        lost.push("subgraphs");
+    }
+    let flat = subgraph::flatten(graph);
+    if flat.get_nodes().iter().any(|n| !n.keyframes.is_empty()) {
+        lost.push("keyframes");
+    }
+    if !timeline.is_default() {
+        lost.push("the timeline");
+    }
+    lost
+}
+
+/// Prints what each file is made of, with subgraphs expanded: how many
+/// nodes and links, the node types used and, given a catalog, the ones it
+/// doesn't have.
+fn inspect(args: &[String]) -> i32 {
+    let args = match file_args(args, &["--catalog"], INSPECT_USAGE) {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+    let mut code = EXIT_OK;
+    for path in &args.files {
+        match read_project(path) {
+            Ok((graph, timeline)) => print_summary(path, &graph, &timeline, args.catalog.as_ref()),
+            Err(e) => {
+                eprintln!("Could not open {}: {}", path, e);
+                code = EXIT_INVALID;
+            }
+        }
+    }
+    code
+}
+
+fn print_summary(path: &str, root: &Graph, timeline: &Timeline, catalog: Option<&Catalog>) {
+    let graph = subgraph::flatten(root);
+    let mut types = BTreeMap::<&NodeType, usize>::new();
+    for node in graph.get_nodes() {
+        *types.entry(&node.ty).or_default() += 1;
+    }
+    let inactive = graph
+        .get_nodes()
+        .iter()
+        .filter(|n| !n.mode.is_active())
+        .count();
+
+    println!("{}", path);
+    println!(
+        "  nodes: {} ({} inactive), links: {}, groups: {}",
+        graph.get_nodes().len(),
+        inactive,
+        graph.get_links().len(),
+        root.get_groups().len()
+    );
+    println!(
+        "  subgraphs: {}, timeline segments: {}",
+        root.subgraphs().len(),
+        timeline.segments().len()
+    );
+    println!("  types:");
+    for (ty, count) in &types {
+        println!("    {:>4} {}", count, ty.0);
+    }
+    if let Some(catalog) = catalog {
+        let missing = types
+            .keys()
+            .filter(|ty| !ty.is_note() && !ty.is_reroute() && !catalog.contains_key(ty))
+            .map(|ty| ty.0.as_str())
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            println!("  missing types: none");
+        } else {
+            println!("  missing types: {}", missing.join(", "));
+        }
+    }
+}
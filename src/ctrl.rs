@@ -1,36 +1,110 @@
-use self::{command_palette::CommandPalette, graph::Graph, menu::Menu, tabs::Tabs};
+use self::{
+    command_palette::CommandPalette, graph::Graph, menu::Menu, tabs::Tabs, templates::Templates,
+    timeline::Timeline,
+};
 use crate::{
-    model::{self, Link, LinkType, Model, Node, NodeType},
+    model::{
+        self, Alignment, Axis, DanglingLink, ExportOptions, Interpolation, Link, Model, Node,
+        NodeMode, NodeType, Project, Segment, Template,
+    },
     ui::View,
-    utils::{Aro, Arw},
+    utils::{self, Aro, Arw},
 };
+use serde_json::Value;
 use slint::{ComponentHandle, Weak};
 use std::{
     collections::HashMap,
-    fs::File,
-    sync::mpsc::{Receiver, Sender},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{Receiver, Sender},
+    },
 };
 
 mod command_palette;
 mod graph;
 mod menu;
 mod tabs;
+mod templates;
+mod timeline;
 
 #[derive(Debug)]
 pub enum Event {
     SetNodePosition(usize, f32, f32),
-    AddNode(NodeType),
+    SetNoteText(usize, String),
+    AddNode(NodeType, (f32, f32)),
+    AddLinkedNode(NodeType, (f32, f32), DanglingLink),
     AddLink(Link),
     RemoveLink(usize),
+    InsertReroute(usize, (f32, f32)),
     SelectTab(usize),
     CloseTab(usize),
     NewTab,
+    NewTabFromTemplate(String),
     SetCommandSearch(String),
     SetZoom(f32),
     SetOffset(f32, f32),
     Save,
     SaveAs,
+    ExportWorkflow,
+    ExportTimeline(ExportOptions),
     OpenFile,
+    OpenPath(String),
+    ClearRecentFiles,
+    Undo,
+    Redo,
+    ZoomToFit(f32, f32),
+    ZoomToSelection(f32, f32),
+    CenterNode(usize, f32, f32),
+    SaveBookmark(String, Option<u8>),
+    GoToBookmark(String),
+    SelectNode(usize, bool),
+    SelectInRect((f32, f32, f32, f32), bool),
+    ClearSelection,
+    AutoLayout,
+    Align(Alignment),
+    Distribute(Axis),
+    ToggleSnapToGrid,
+    ToggleNodeMode(NodeMode),
+    GroupSelection,
+    CollapseSelection,
+    OpenSubgraph(usize),
+    LeaveSubgraph(usize),
+    MoveGroup(usize, f32, f32),
+    ResizeGroup(usize, f32, f32),
+    RenameGroup(usize, String),
+    CycleGroupColor(usize),
+    RemoveGroup(usize),
+    SaveTemplate(String, String, Vec<String>),
+    InsertTemplate(String, (f32, f32)),
+    AddSegment(Segment),
+    /// Segment index, start and track.
+    MoveSegment(usize, f32, usize),
+    TrimSegment(usize, f32, f32),
+    RemoveSegment(usize),
+    SetFps(f32),
+    AddTrack,
+    RemoveTrack(usize),
+    MoveTrack(usize, usize),
+    RenameTrack(usize, String),
+    ToggleTrackMute(usize),
+    ToggleTrackLock(usize),
+    /// Widget, time, value and interpolation.
+    SetKeyframe(String, f32, serde_json::Value, Interpolation),
+    RemoveKeyframes(f32),
+    /// Time of the playhead, keyframed widgets take their value there.
+    QueuePrompt(f32),
+    RenderRange,
+    CancelRender,
+    /// Ticket of the prompt and the images it produced, by prompt node id.
+    PromptFinished(String, HashMap<String, Vec<String>>),
+    /// Ticket of the range and the ids the backend gave its prompts.
+    RenderQueued(String, Vec<String>),
+    /// Ticket of the range and how many of its frames are done.
+    RenderProgress(String, usize),
+    /// Ticket of the range, start of the range and one image per frame.
+    RenderFinished(String, f32, Vec<String>),
+    /// Ticket of the prompt or range and what went wrong.
+    PromptFailed(String, String),
 }
 
 trait Controller {
@@ -39,13 +113,14 @@ trait Controller {
 }
 
 pub struct Mediator {
+    tx: Sender<Event>,
     rx: Receiver<Event>,
     model: Arw<Model>,
     ui: Weak<View>,
 }
 
 impl Mediator {
-    pub fn new(ui: &View, mut model: Model) -> Self {
+    pub fn new(ui: &View, mut model: Model, paths: Vec<String>) -> Self {
         let (tx, rx) = std::sync::mpsc::channel();
 
         populate_available_nodes(&mut model);
@@ -57,8 +132,15 @@ impl Mediator {
         Tabs::setup(ro_model.clone(), ui, tx.clone());
         Graph::setup(ro_model.clone(), ui, tx.clone());
         CommandPalette::setup(ro_model.clone(), ui, tx.clone());
+        Templates::setup(ro_model.clone(), ui, tx.clone());
+        Timeline::setup(ro_model.clone(), ui, tx.clone());
+
+        for path in paths {
+            tx.send(Event::OpenPath(path)).unwrap();
+        }
 
         Self {
+            tx,
             rx,
             model,
             ui: ui.as_weak(),
@@ -90,22 +172,39 @@ impl Mediator {
                     notify!(CommandPalette);
                 }
                 SetNodePosition(node_idx, x, y) => {
+                    let mut model = self.model.write();
+                    let grid = model.settings().grid();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.move_node(node_idx, (x, y), grid);
+                    }
+                    notify!(Graph);
+                }
+                SetNoteText(node_idx, ref text) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.checkpoint();
+                        project.graph_mut().set_node_text(node_idx, text.clone());
+                    }
+                    notify!(Graph);
+                }
+                AddNode(ref ty, pos) => {
                     let mut model = self.model.write();
                     if let Some(project) = model.tabs_mut().selected_project_mut() {
-                        project.graph_mut().set_node_position(node_idx, x, y);
+                        project.add_node(ty.clone(), pos);
                     }
                     notify!(Graph);
                 }
-                AddNode(ref ty) => {
+                AddLinkedNode(ref ty, pos, ref link) => {
                     let mut model = self.model.write();
                     if let Some(project) = model.tabs_mut().selected_project_mut() {
-                        project.graph_mut().add_node(ty.clone());
+                        project.add_linked_node(ty.clone(), pos, link.clone());
                     }
                     notify!(Graph);
                 }
                 AddLink(ref lnk) => {
                     let mut model = self.model.write();
                     if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.checkpoint();
                         project.graph_mut().add_link(lnk.clone());
                     }
                     notify!(Graph);
@@ -113,61 +212,121 @@ impl Mediator {
                 RemoveLink(i) => {
                     let mut model = self.model.write();
                     if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.checkpoint();
                         project.graph_mut().remove_link(i);
                     }
                     notify!(Graph);
                 }
+                InsertReroute(link_idx, pos) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.insert_reroute(link_idx, pos);
+                    }
+                    notify!(Graph);
+                }
                 SelectTab(i) => {
                     let mut model = self.model.write();
                     model.tabs_mut().select_tab(i);
-                    notify!(Graph, Tabs, CommandPalette);
+                    notify!(Graph, Tabs, CommandPalette, Timeline);
                 }
                 NewTab => {
                     let mut model = self.model.write();
                     model.tabs_mut().new_tab();
                     populate_available_nodes(&mut model);
-                    notify!(Graph, Tabs, CommandPalette);
+                    notify!(Graph, Tabs, CommandPalette, Timeline);
+                }
+                NewTabFromTemplate(ref name) => {
+                    let mut model = self.model.write();
+                    if let Some(template) = model.templates().get(name) {
+                        let project = Project::from_graph(template.graph.clone());
+                        model.tabs_mut().open_tab(project);
+                        populate_available_nodes(&mut model);
+                    }
+                    notify!(Graph, Tabs, CommandPalette, Timeline);
                 }
                 CloseTab(i) => {
                     let mut model = self.model.write();
                     model.tabs_mut().close_tab(i);
-                    notify!(Graph, Tabs, CommandPalette);
+                    notify!(Graph, Tabs, CommandPalette, Timeline);
                 }
                 Save => {
                     let mut model = self.model.write();
+                    let mut saved = None;
                     if let Some(selected) = model.tabs_mut().selected_project_mut() {
                         if let Some(path) = selected.file_path() {
-                            save_graph(path, selected.graph());
-                        } else if let Some(path) = save_dialog() {
-                            selected.set_file_path(path.clone());
-                            save_graph(&path, selected.graph());
+                            if save_project(path, selected) {
+                                saved = Some(path.to_owned());
+                            }
+                        } else if let Some(path) = save_dialog("Kira Graph File", &["kira"]) {
+                            if save_project(&path, selected) {
+                                selected.set_file_path(path.clone());
+                                saved = Some(path);
+                            }
                         }
                     }
-                    notify!(Tabs);
+                    if let Some(path) = saved {
+                        model.recent_files_mut().push(&path);
+                    }
+                    notify!(Tabs, Menu);
                 }
                 SaveAs => {
                     let mut model = self.model.write();
+                    let mut saved = None;
                     if let Some(selected) = model.tabs_mut().selected_project_mut() {
-                        if let Some(path) = save_dialog() {
-                            selected.set_file_path(path.clone());
-                            save_graph(&path, selected.graph());
+                        if let Some(path) = save_dialog("Kira Graph File", &["kira"]) {
+                            if save_project(&path, selected) {
+                                selected.set_file_path(path.clone());
+                                saved = Some(path);
+                            }
+                        }
+                    }
+                    if let Some(path) = saved {
+                        model.recent_files_mut().push(&path);
+                    }
+                    notify!(Tabs, Menu);
+                }
+                ExportWorkflow => {
+                    let model = self.model.read();
+                    if let Some(project) = model.tabs().selected_project() {
+                        if let Some(path) = save_dialog("ComfyUI Workflow", &["json"]) {
+                            if let Err(e) = project.export_workflow(&path) {
+                                error_dialog(format!("Could not export {}: {}", path, e));
+                            }
+                        }
+                    }
+                }
+                ExportTimeline(ref options) => {
+                    let model = self.model.read();
+                    if let Some(project) = model.tabs().selected_project() {
+                        let (filter, extensions) = options.format.filter();
+                        if let Some(path) = save_dialog(filter, extensions) {
+                            let timeline = project.timeline().clone();
+                            let options = options.clone();
+                            // encoding every frame takes a while
+                            std::thread::spawn(move || {
+                                if let Err(e) = model::export_timeline(&timeline, &path, &options) {
+                                    error_dialog(format!("Could not export {}: {}", path, e));
+                                }
+                            });
                         }
                     }
-                    notify!(Tabs);
                 }
                 OpenFile => {
                     let mut model = self.model.write();
                     if let Some(path) = open_dialog() {
-                        // TODO: refactor project initialization into model
-                        let graph = read_graph(&path);
-                        model.tabs_mut().new_tab();
-                        let selected = model.tabs_mut().selected_project_mut().unwrap();
-                        dbg!(&graph);
-                        *selected.graph_mut() = graph;
-                        selected.set_file_path(path.clone());
-                        populate_available_nodes(&mut model);
+                        open_path(&mut model, &path);
                     }
-                    notify!(Graph, Tabs, CommandPalette);
+                    notify!(Graph, Tabs, CommandPalette, Timeline, Menu);
+                }
+                OpenPath(ref path) => {
+                    let mut model = self.model.write();
+                    open_path(&mut model, path);
+                    notify!(Graph, Tabs, CommandPalette, Timeline, Menu);
+                }
+                ClearRecentFiles => {
+                    let mut model = self.model.write();
+                    model.recent_files_mut().clear();
+                    notify!(Menu);
                 }
                 SetZoom(zoom) => {
                     let mut model = self.model.write();
@@ -183,11 +342,518 @@ impl Mediator {
                     }
                     notify!(Graph);
                 }
+                Undo => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.undo();
+                    }
+                    notify!(Graph, Timeline);
+                }
+                Redo => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.redo();
+                    }
+                    notify!(Graph, Timeline);
+                }
+                ZoomToFit(width, height) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.zoom_to_fit((width, height));
+                    }
+                    notify!(Graph);
+                }
+                ZoomToSelection(width, height) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.zoom_to_selection((width, height));
+                    }
+                    notify!(Graph);
+                }
+                CenterNode(idx, width, height) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.center_node(idx, (width, height));
+                    }
+                    notify!(Graph);
+                }
+                SaveBookmark(ref name, key) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.checkpoint();
+                        project.graph_mut().save_bookmark(name.clone(), key);
+                    }
+                }
+                GoToBookmark(ref name) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.graph_mut().go_to_bookmark(name);
+                    }
+                    notify!(Graph);
+                }
+                SelectNode(idx, extend) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.graph_mut().select_node(idx, extend);
+                    }
+                    notify!(Graph);
+                }
+                SelectInRect(rect, extend) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.select_in_rect(rect, extend);
+                    }
+                    notify!(Graph);
+                }
+                ClearSelection => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.graph_mut().clear_selection();
+                    }
+                    notify!(Graph);
+                }
+                AutoLayout => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.auto_layout();
+                    }
+                    notify!(Graph);
+                }
+                Align(alignment) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.align_selection(alignment);
+                    }
+                    notify!(Graph);
+                }
+                Distribute(axis) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.distribute_selection(axis);
+                    }
+                    notify!(Graph);
+                }
+                ToggleSnapToGrid => {
+                    let mut model = self.model.write();
+                    model.settings_mut().toggle_snap_to_grid();
+                }
+                ToggleNodeMode(mode) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.toggle_mode(mode);
+                    }
+                    notify!(Graph);
+                }
+                GroupSelection => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.group_selection();
+                    }
+                    notify!(Graph);
+                }
+                CollapseSelection => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.collapse_selection();
+                    }
+                    notify!(Graph, CommandPalette);
+                }
+                OpenSubgraph(idx) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.open_subgraph(idx);
+                    }
+                    notify!(Graph);
+                }
+                LeaveSubgraph(depth) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.leave_subgraph(depth);
+                    }
+                    notify!(Graph);
+                }
+                MoveGroup(idx, x, y) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.move_group(idx, (x, y));
+                    }
+                    notify!(Graph);
+                }
+                ResizeGroup(idx, width, height) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.resize_group(idx, (width, height));
+                    }
+                    notify!(Graph);
+                }
+                RenameGroup(idx, ref title) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.checkpoint();
+                        project.graph_mut().set_group_title(idx, title.clone());
+                    }
+                    notify!(Graph);
+                }
+                CycleGroupColor(idx) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.checkpoint();
+                        project.graph_mut().cycle_group_color(idx);
+                    }
+                    notify!(Graph);
+                }
+                RemoveGroup(idx) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.checkpoint();
+                        project.graph_mut().remove_group(idx);
+                    }
+                    notify!(Graph);
+                }
+                SaveTemplate(ref name, ref description, ref tags) => {
+                    let mut model = self.model.write();
+                    if let Some(graph) = model.tabs().selected_project().map(Project::snapshot) {
+                        let template = Template {
+                            name: name.clone(),
+                            description: description.clone(),
+                            tags: tags.clone(),
+                            graph,
+                        };
+                        if let Err(e) = model.templates_mut().save(template) {
+                            error_dialog(format!("Could not save template {}: {}", name, e));
+                        }
+                    }
+                }
+                InsertTemplate(ref name, pos) => {
+                    let mut model = self.model.write();
+                    if let Some(graph) = model.templates().get(name).map(|t| t.graph.clone()) {
+                        match model.tabs_mut().selected_project_mut() {
+                            Some(project) => project.insert_graph(graph, pos),
+                            // nothing to insert into, start a graph from it instead
+                            None => {
+                                model.tabs_mut().open_tab(Project::from_graph(graph));
+                                populate_available_nodes(&mut model);
+                            }
+                        }
+                    }
+                    notify!(Graph, Tabs, CommandPalette, Timeline);
+                }
+                AddSegment(ref segment) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.add_segment(segment.clone()));
+                    }
+                    notify!(Timeline);
+                }
+                MoveSegment(idx, start, track) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.move_segment(idx, start, track));
+                    }
+                    notify!(Timeline);
+                }
+                TrimSegment(idx, start, duration) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.trim_segment(idx, start, duration));
+                    }
+                    notify!(Timeline);
+                }
+                RemoveSegment(idx) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.remove_segment(idx));
+                    }
+                    notify!(Timeline);
+                }
+                SetFps(fps) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.set_fps(fps));
+                    }
+                    notify!(Timeline);
+                }
+                AddTrack => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.add_track());
+                    }
+                    notify!(Timeline);
+                }
+                RemoveTrack(idx) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.remove_track(idx));
+                    }
+                    notify!(Timeline);
+                }
+                MoveTrack(from, to) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.move_track(from, to));
+                    }
+                    notify!(Timeline);
+                }
+                RenameTrack(idx, ref name) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.rename_track(idx, name.clone()));
+                    }
+                    notify!(Timeline);
+                }
+                ToggleTrackMute(idx) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.toggle_track_mute(idx));
+                    }
+                    notify!(Timeline);
+                }
+                ToggleTrackLock(idx) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.edit_timeline(|t| t.toggle_track_lock(idx));
+                    }
+                    notify!(Timeline);
+                }
+                SetKeyframe(ref widget, time, ref value, interpolation) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.keyframe_selection(
+                            widget.clone(),
+                            time,
+                            value.clone(),
+                            interpolation,
+                        );
+                    }
+                    notify!(Timeline);
+                }
+                RemoveKeyframes(time) => {
+                    let mut model = self.model.write();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        project.remove_selection_keyframes(time);
+                    }
+                    notify!(Timeline);
+                }
+                QueuePrompt(time) => {
+                    let mut model = self.model.write();
+                    let backend = model.backend().clone();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        let problems = project.problems();
+                        if !problems.is_empty() {
+                            error_dialog(format!(
+                                "Could not queue prompt: {}",
+                                problems.join(", ")
+                            ));
+                            continue;
+                        }
+                        let ticket = new_ticket();
+                        project.prompt_queued(ticket.clone());
+                        send_prompt(backend, project.to_prompt_at(time), ticket, self.tx.clone());
+                    }
+                }
+                RenderRange => {
+                    let mut model = self.model.write();
+                    let backend = model.backend().clone();
+                    if let Some(project) = model.tabs_mut().selected_project_mut() {
+                        let problems = project.problems();
+                        if !problems.is_empty() {
+                            error_dialog(format!("Could not render: {}", problems.join(", ")));
+                            continue;
+                        }
+                        let prompts = project.render_prompts();
+                        let Some(&(start, _)) = prompts.first() else {
+                            error_dialog("There are no keyframes to render".into());
+                            continue;
+                        };
+                        let ticket = new_ticket();
+                        project.render_started(ticket.clone(), prompts.len());
+                        let prompts = prompts.into_iter().map(|(_, prompt)| prompt).collect();
+                        send_render(backend, prompts, start, ticket, self.tx.clone());
+                    }
+                    notify!(Timeline);
+                }
+                CancelRender => {
+                    let mut model = self.model.write();
+                    let backend = model.backend().clone();
+                    let ids = model
+                        .tabs_mut()
+                        .selected_project_mut()
+                        .and_then(|p| p.cancel_render());
+                    // prompts still being queued are cancelled once they are
+                    if let Some(ids) = ids.filter(|ids| !ids.is_empty()) {
+                        cancel_prompts(backend, ids);
+                    }
+                }
+                RenderQueued(ref ticket, ref ids) => {
+                    let mut model = self.model.write();
+                    let backend = model.backend().clone();
+                    for project in model.tabs_mut().projects_mut() {
+                        if let Some(cancelled) = project.render_queued(ticket, ids.clone()) {
+                            cancel_prompts(backend, cancelled);
+                            break;
+                        }
+                    }
+                }
+                RenderProgress(ref ticket, done) => {
+                    let mut model = self.model.write();
+                    for project in model.tabs_mut().projects_mut() {
+                        project.render_progressed(ticket, done);
+                    }
+                    notify!(Timeline);
+                }
+                PromptFinished(ref id, ref outputs) => {
+                    let mut model = self.model.write();
+                    for project in model.tabs_mut().projects_mut() {
+                        if project.finish_prompt(id, outputs) {
+                            break;
+                        }
+                    }
+                    notify!(Graph);
+                }
+                RenderFinished(ref id, start, ref frames) => {
+                    let mut model = self.model.write();
+                    for project in model.tabs_mut().projects_mut() {
+                        if project.finish_render(id, start, frames.clone()) {
+                            break;
+                        }
+                    }
+                    notify!(Timeline);
+                }
+                PromptFailed(ref ticket, ref message) => {
+                    let mut model = self.model.write();
+                    let mut cancelled = false;
+                    for project in model.tabs_mut().projects_mut() {
+                        cancelled = project.is_cancelled(ticket);
+                        if project.prompt_failed(ticket) {
+                            break;
+                        }
+                    }
+                    // a cancelled render fails on purpose
+                    if !cancelled {
+                        error_dialog(message.clone());
+                    }
+                    notify!(Timeline);
+                }
             }
         }
     }
 }
 
+/// Prompts are tracked by a ticket of our own from the moment they are
+/// sent, the backend only hands out its id once it has queued them.
+fn new_ticket() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    format!("ticket-{}", NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Queues `prompt` and waits for it on another thread, so an unreachable
+/// backend doesn't hold up the editor. Reports the images it produced,
+/// which are kept in the data directory.
+fn send_prompt(backend: model::Backend, prompt: Value, ticket: String, tx: Sender<Event>) {
+    std::thread::spawn(move || {
+        let id = match backend.queue_prompt(&prompt) {
+            Ok(id) => id,
+            Err(e) => {
+                let message = format!("Could not queue prompt: {}", e);
+                tx.send(Event::PromptFailed(ticket, message)).unwrap();
+                return;
+            }
+        };
+        let dir = utils::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("outputs")
+            .join(&id);
+        match backend.wait_for_outputs(&id, &dir) {
+            Ok(outputs) => {
+                let outputs = outputs
+                    .into_iter()
+                    .map(|(node, paths)| {
+                        let paths = paths
+                            .iter()
+                            .map(|p| p.to_string_lossy().into_owned())
+                            .collect();
+                        (node, paths)
+                    })
+                    .collect();
+                tx.send(Event::PromptFinished(ticket, outputs)).unwrap();
+            }
+            Err(e) => {
+                let message = format!("Could not fetch the outputs of prompt {}: {}", id, e);
+                tx.send(Event::PromptFailed(ticket, message)).unwrap();
+            }
+        }
+    });
+}
+
+/// Queues the prompts of a rendered range on another thread, then waits
+/// for them in turn and reports the first image each of them produced as a
+/// frame. A frame going missing would shift all the later ones, so the
+/// render stops at the first prompt that fails and takes the rest off the
+/// queue.
+fn send_render(
+    backend: model::Backend,
+    prompts: Vec<Value>,
+    start: f32,
+    ticket: String,
+    tx: Sender<Event>,
+) {
+    std::thread::spawn(move || {
+        let fail = |ids: &[String], message: String| {
+            // best effort, the prompts may be gone already
+            backend.cancel(ids).ok();
+            tx.send(Event::PromptFailed(ticket.clone(), message))
+                .unwrap();
+        };
+        let mut ids = vec![];
+        for prompt in &prompts {
+            match backend.queue_prompt(prompt) {
+                Ok(id) => ids.push(id),
+                Err(e) => return fail(&ids, format!("Could not queue prompt: {}", e)),
+            }
+        }
+        tx.send(Event::RenderQueued(ticket.clone(), ids.clone()))
+            .unwrap();
+
+        let dir = utils::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("outputs")
+            .join(&ids[0]);
+        let mut frames = vec![];
+        for (frame, id) in ids.iter().enumerate() {
+            // the output node that comes first in the prompt
+            let image = backend
+                .wait_for_outputs(id, &dir.join(id))
+                .and_then(|outputs| {
+                    outputs
+                        .into_iter()
+                        .min_by_key(|(node, _)| node.parse::<usize>().unwrap_or(usize::MAX))
+                        .and_then(|(_, paths)| paths.into_iter().next())
+                        .ok_or_else(|| format!("prompt {} produced no image", id).into())
+                });
+            match image {
+                Ok(image) => frames.push(image.to_string_lossy().into_owned()),
+                Err(e) => {
+                    let message = format!("Could not render frame {}: {}", frame + 1, e);
+                    return fail(&ids[frame + 1..], message);
+                }
+            }
+            tx.send(Event::RenderProgress(ticket.clone(), frame + 1))
+                .unwrap();
+        }
+        tx.send(Event::RenderFinished(ticket.clone(), start, frames))
+            .unwrap();
+    });
+}
+
+/// Takes prompts off the queue of the backend on another thread.
+fn cancel_prompts(backend: model::Backend, ids: Vec<String>) {
+    std::thread::spawn(move || {
+        if let Err(e) = backend.cancel(&ids) {
+            error_dialog(format!("Could not cancel the render: {}", e));
+        }
+    });
+}
+
 fn open_dialog() -> Option<String> {
     // TODO: better error handling
     let (tx, rx) = std::sync::mpsc::channel();
@@ -195,6 +861,7 @@ fn open_dialog() -> Option<String> {
         tx.send(
             native_dialog::FileDialog::new()
                 .add_filter("Kira Graph File", &["kira"])
+                .add_filter("ComfyUI Workflow", &["json", "png"])
                 .show_open_single_file()
                 .ok()
                 .flatten()
@@ -206,13 +873,13 @@ fn open_dialog() -> Option<String> {
     rx.recv().unwrap()
 }
 
-fn save_dialog() -> Option<String> {
+fn save_dialog(filter: &'static str, extensions: &'static [&'static str]) -> Option<String> {
     // TODO: better error handling
     let (tx, rx) = std::sync::mpsc::channel();
     slint::invoke_from_event_loop(move || {
         tx.send(
             native_dialog::FileDialog::new()
-                .add_filter("Kira Graph File", &["kira"])
+                .add_filter(filter, extensions)
                 .show_save_single_file()
                 .ok()
                 .flatten()
@@ -224,16 +891,37 @@ fn save_dialog() -> Option<String> {
     rx.recv().unwrap()
 }
 
-fn save_graph(path: &str, graph: &model::Graph) {
-    // TODO: better error handling
-    let f = File::create(path).unwrap();
-    serde_json::to_writer(f, graph).unwrap();
+fn error_dialog(text: String) {
+    slint::invoke_from_event_loop(move || {
+        native_dialog::MessageDialog::new()
+            .set_type(native_dialog::MessageType::Error)
+            .set_title("Kira Studio")
+            .set_text(&text)
+            .show_alert()
+            .ok();
+    })
+    .unwrap();
 }
 
-fn read_graph(path: &str) -> model::Graph {
-    // TODO: better error handling
-    let f = File::open(path).unwrap();
-    serde_json::from_reader(f).unwrap()
+fn save_project(path: &str, project: &Project) -> bool {
+    match model::save_project(path, project.root_graph(), project.timeline()) {
+        Ok(()) => true,
+        Err(e) => {
+            error_dialog(format!("Could not save {}: {}", path, e));
+            false
+        }
+    }
+}
+
+fn open_path(model: &mut Model, path: &str) {
+    match Project::open(path) {
+        Ok(project) => {
+            model.tabs_mut().open_tab(project);
+            model.recent_files_mut().push(path);
+            populate_available_nodes(model);
+        }
+        Err(e) => error_dialog(format!("Could not open {}: {}", path, e)),
+    }
 }
 
 fn populate_available_nodes(model: &mut Model) {
@@ -247,6 +935,7 @@ fn populate_available_nodes(model: &mut Model) {
                     ("Text".into(), "TXT".into()),
                     ("Image".into(), "IMG".into()),
                 ],
+                widgets: vec![],
                 outputs: vec![
                     ("Text".into(), "TXT".into()),
                     ("Image".into(), "IMG".into()),
@@ -254,6 +943,7 @@ fn populate_available_nodes(model: &mut Model) {
                 name,
                 description: "Node of type A".into(),
                 category: "Dummy".into(),
+                pack: "dummy".into(),
             },
         );
         let name = format!("B{}", i);
@@ -261,10 +951,12 @@ fn populate_available_nodes(model: &mut Model) {
             NodeType(name.clone()),
             Node {
                 inputs: vec![("Image".into(), "IMG".into())],
+                widgets: vec![],
                 outputs: vec![],
                 name,
                 description: "Node of type B".into(),
                 category: "Dummy".into(),
+                pack: "dummy".into(),
             },
         );
         let name = format!("C{}", i);
@@ -272,43 +964,17 @@ fn populate_available_nodes(model: &mut Model) {
             NodeType(name.clone()),
             Node {
                 inputs: vec![],
+                widgets: vec![],
                 outputs: vec![("Text".into(), "TXT".into()), ("Text".into(), "TXT".into())],
                 name,
                 description: "Node of type C".into(),
                 category: "Dummy".into(),
+                pack: "dummy".into(),
             },
         );
     }
 
-    let available_nodes = model
-        .backend()
-        .query_available_nodes()
-        .map(|hm| {
-            hm.into_iter()
-                .map(|(k, v)| {
-                    (
-                        NodeType(k),
-                        Node {
-                            inputs: v
-                                .input
-                                .into_iter()
-                                .map(|(lbl, ty)| (lbl, LinkType(ty)))
-                                .collect(),
-                            outputs: v
-                                .output_name
-                                .into_iter()
-                                .zip(v.output)
-                                .map(|(lbl, ty)| (lbl, LinkType(ty)))
-                                .collect(),
-                            name: v.display_name,
-                            description: v.description,
-                            category: v.category,
-                        },
-                    )
-                })
-                .collect()
-        })
-        .unwrap_or(dummy_nodes);
+    let available_nodes = model.backend().available_nodes().unwrap_or(dummy_nodes);
     if let Some(project) = model.tabs_mut().selected_project_mut() {
         project.set_available_nodes(available_nodes);
     }
@@ -1,7 +1,10 @@
 use super::{Aro, Controller, Event};
 use crate::{
-    model::Model,
-    ui::{PaletteSearch, SearchItem, View},
+    model::{
+        Alignment, Axis, Command, DanglingLink, LinkType, Model, NodeMode, PaletteEntry,
+        PaletteQuery, Shortcut,
+    },
+    ui::{CommandLogic, FloatingState, PaletteSearch, SearchItem, SearchItemKind, View},
 };
 use slint::{ComponentHandle, ModelRc, VecModel};
 use std::sync::mpsc::Sender;
@@ -10,8 +13,8 @@ pub struct CommandPalette;
 
 impl Controller for CommandPalette {
     fn setup(model: Aro<Model>, ui: &View, tx: Sender<Event>) {
+        setup_command_palette_logic(ui, model.clone(), tx);
         let model = model.read();
-        setup_command_palette_logic(ui, tx);
         refresh(&model, ui);
     }
     fn notify(ui: &View, model: &Model, _evt: &Event) {
@@ -19,7 +22,7 @@ impl Controller for CommandPalette {
     }
 }
 
-fn setup_command_palette_logic(ui: &View, tx: Sender<Event>) {
+fn setup_command_palette_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
     ui.global::<PaletteSearch>().on_search({
         let tx = tx.clone();
         move |query| {
@@ -33,28 +36,333 @@ fn setup_command_palette_logic(ui: &View, tx: Sender<Event>) {
         }
     });
 
-    ui.global::<PaletteSearch>().on_add_node({
-        move |id| {
-            tx.send(Event::AddNode(id.clone().into())).unwrap();
+    ui.global::<PaletteSearch>().on_activate({
+        let ui = ui.as_weak();
+        let model = model.clone();
+        let tx = tx.clone();
+        move |item| {
+            let ui = ui.upgrade().unwrap();
+            match item.kind {
+                SearchItemKind::Node => {
+                    let pos = placement_pos(&ui);
+                    let evt = match dangling_link(&ui) {
+                        Some(link) => Event::AddLinkedNode(item.id.as_str().into(), pos, link),
+                        None => Event::AddNode(item.id.as_str().into(), pos),
+                    };
+                    tx.send(evt).unwrap();
+                }
+                SearchItemKind::Template => {
+                    let pos = placement_pos(&ui);
+                    tx.send(Event::InsertTemplate(item.id.clone().into(), pos))
+                        .unwrap();
+                }
+                SearchItemKind::TemplateTab => {
+                    tx.send(Event::NewTabFromTemplate(item.id.clone().into()))
+                        .unwrap();
+                }
+                SearchItemKind::GraphNode => {
+                    if let Ok(idx) = item.id.parse() {
+                        tx.send(Event::CenterNode(
+                            idx,
+                            ui.get_graph_width(),
+                            ui.get_graph_height(),
+                        ))
+                        .unwrap();
+                    }
+                }
+                SearchItemKind::Bookmark => {
+                    tx.send(Event::GoToBookmark(item.id.clone().into()))
+                        .unwrap();
+                }
+                SearchItemKind::NewBookmark => {
+                    tx.send(Event::SaveBookmark(item.id.clone().into(), None))
+                        .unwrap();
+                }
+                SearchItemKind::Command => {
+                    if let Some(command) = Command::from_id(&item.id) {
+                        run_command(command, &ui, &model.read(), &tx);
+                    }
+                }
+            }
+        }
+    });
+
+    ui.global::<CommandLogic>().on_shortcut({
+        let ui = ui.as_weak();
+        move |key, ctrl, shift, alt| {
+            let ui = ui.upgrade().unwrap();
+            if let Some(evt) = bookmark_shortcut(&key, ctrl, shift, alt, &model.read()) {
+                tx.send(evt).unwrap();
+                return true;
+            }
+            match Command::from_shortcut(&Shortcut::new(&key, ctrl, shift, alt)) {
+                Some(command) => {
+                    run_command(command, &ui, &model.read(), &tx);
+                    true
+                }
+                None => false,
+            }
         }
     });
 }
 
+/// Number keys recall the bookmark bound to them, and with Alt save the
+/// current view there.
+fn bookmark_shortcut(
+    key: &str,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    model: &Model,
+) -> Option<Event> {
+    let key = key.parse::<u8>().ok().filter(|k| (1..=9).contains(k))?;
+    if ctrl || shift {
+        return None;
+    }
+    let graph = model.tabs().selected_project()?.graph();
+    let bookmark = graph.bookmark_with_key(key).map(|b| b.name.clone());
+    if alt {
+        let name = bookmark.unwrap_or_else(|| format!("View {}", key));
+        Some(Event::SaveBookmark(name, Some(key)))
+    } else {
+        bookmark.map(Event::GoToBookmark)
+    }
+}
+
+/// The link being dragged when the palette was opened, if any.
+fn dangling_link(ui: &View) -> Option<DanglingLink> {
+    let floating = ui.get_floating();
+    let node = floating.node as usize;
+    let slot = floating.node_slot as usize;
+    let ty = LinkType(floating.ty.into());
+    match floating.floating_state {
+        FloatingState::SrcAttached => Some(DanglingLink::FromOutput { node, slot, ty }),
+        FloatingState::DstAttached => Some(DanglingLink::ToInput { node, slot, ty }),
+        FloatingState::None => None,
+    }
+}
+
+/// Converts the point new nodes should be placed at into graph coordinates.
+fn placement_pos(ui: &View) -> (f32, f32) {
+    let zoom = ui.get_zoom();
+    (
+        ui.get_place_x() / zoom - ui.get_offset_x(),
+        ui.get_place_y() / zoom - ui.get_offset_y() + 10.,
+    )
+}
+
+fn run_command(command: Command, ui: &View, model: &Model, tx: &Sender<Event>) {
+    let evt = match command {
+        Command::OpenFile => Some(Event::OpenFile),
+        Command::Save => Some(Event::Save),
+        Command::SaveAs => Some(Event::SaveAs),
+        Command::ExportWorkflow => Some(Event::ExportWorkflow),
+        Command::ExportTimeline => {
+            ui.as_weak()
+                .upgrade_in_event_loop(|ui| ui.invoke_show_export_dialog())
+                .unwrap();
+            None
+        }
+        Command::NewTab => Some(Event::NewTab),
+        Command::NewTabFromTemplate => {
+            show_palette(ui, "tpl:new");
+            None
+        }
+        Command::SaveAsTemplate => {
+            // after the palette this may have been picked from is closed
+            ui.as_weak()
+                .upgrade_in_event_loop(|ui| ui.invoke_show_template_dialog())
+                .unwrap();
+            None
+        }
+        Command::CloseTab => model.tabs().selected_tab().map(Event::CloseTab),
+        Command::ClearRecentFiles => Some(Event::ClearRecentFiles),
+        Command::Undo => Some(Event::Undo),
+        Command::Redo => Some(Event::Redo),
+        Command::ZoomToFit => Some(Event::ZoomToFit(
+            ui.get_graph_width(),
+            ui.get_graph_height(),
+        )),
+        Command::AutoLayout => Some(Event::AutoLayout),
+        Command::AlignLeft => Some(Event::Align(Alignment::Left)),
+        Command::AlignTop => Some(Event::Align(Alignment::Top)),
+        Command::AlignCenter => Some(Event::Align(Alignment::Center)),
+        Command::AlignMiddle => Some(Event::Align(Alignment::Middle)),
+        Command::DistributeHorizontally => Some(Event::Distribute(Axis::Horizontal)),
+        Command::DistributeVertically => Some(Event::Distribute(Axis::Vertical)),
+        Command::ToggleSnapToGrid => Some(Event::ToggleSnapToGrid),
+        Command::ToggleMute => Some(Event::ToggleNodeMode(NodeMode::Muted)),
+        Command::ToggleBypass => Some(Event::ToggleNodeMode(NodeMode::Bypassed)),
+        Command::GroupSelection => Some(Event::GroupSelection),
+        Command::CollapseToSubgraph => Some(Event::CollapseSelection),
+        Command::InsertTemplate => {
+            show_palette(ui, "tpl:insert");
+            None
+        }
+        Command::ZoomToSelection => Some(Event::ZoomToSelection(
+            ui.get_graph_width(),
+            ui.get_graph_height(),
+        )),
+        Command::GoToNode => {
+            show_palette(ui, "go:node");
+            None
+        }
+        Command::AddBookmark => {
+            show_palette(ui, "add:bookmark");
+            None
+        }
+        Command::GoToBookmark => {
+            show_palette(ui, "go:bookmark");
+            None
+        }
+        Command::TogglePlayback => {
+            ui.invoke_toggle_playback();
+            None
+        }
+        Command::StopPlayback => {
+            ui.invoke_stop_playback();
+            None
+        }
+        Command::AddKeyframe => {
+            ui.as_weak()
+                .upgrade_in_event_loop(|ui| ui.invoke_show_keyframe_dialog())
+                .unwrap();
+            None
+        }
+        Command::RemoveKeyframe => Some(Event::RemoveKeyframes(ui.get_playhead())),
+        Command::QueuePrompt => Some(Event::QueuePrompt(ui.get_playhead())),
+        Command::RenderRange => Some(Event::RenderRange),
+    };
+    if let Some(evt) = evt {
+        tx.send(evt).unwrap();
+    }
+}
+
+/// Reopens the palette with `filter`, once the palette the command may have
+/// been picked from is closed.
+fn show_palette(ui: &View, filter: &'static str) {
+    ui.as_weak()
+        .upgrade_in_event_loop(move |ui| ui.invoke_show_palette(filter.into()))
+        .unwrap();
+}
+
 fn refresh(model: &Model, ui: &View) {
     let command_search = model.command_search();
     if command_search.is_empty() {
         ui.set_command_palette_results(VecModel::from_slice(&[]))
-    } else if let Some(project) = model.tabs().selected_project() {
-        let res = project
-            .search_available_nodes(command_search)
+    } else {
+        let project = model.tabs().selected_project();
+        let new_tab = PaletteQuery::parse(command_search).new_tab();
+        let res = model
+            .search_palette(command_search)
             .into_iter()
-            .map(|(id, node)| SearchItem {
-                id: id.0.clone().into(),
-                category: node.category.as_str().into(),
-                description: node.description.as_str().into(),
-                name: node.name.as_str().into(),
+            .filter_map(|entry| match entry {
+                PaletteEntry::Node(id) => {
+                    project
+                        .and_then(|p| p.get_available_node(&id))
+                        .map(|node| SearchItem {
+                            kind: SearchItemKind::Node,
+                            id: id.0.clone().into(),
+                            category: node.category.as_str().into(),
+                            description: node.description.as_str().into(),
+                            name: node.name.as_str().into(),
+                            shortcut: "".into(),
+                            inputs: signature(&node.inputs).into(),
+                            outputs: signature(&node.outputs).into(),
+                            tags: "".into(),
+                        })
+                }
+                PaletteEntry::Command(command) => Some(SearchItem {
+                    kind: SearchItemKind::Command,
+                    id: command.id().into(),
+                    category: command.category().into(),
+                    description: command.description().into(),
+                    name: command.name().into(),
+                    shortcut: command
+                        .shortcut()
+                        .map(|s| s.to_string())
+                        .unwrap_or_default()
+                        .into(),
+                    inputs: "".into(),
+                    outputs: "".into(),
+                    tags: "".into(),
+                }),
+                PaletteEntry::GraphNode(idx) => project.map(|p| {
+                    let node = p
+                        .graph()
+                        .get_node(idx)
+                        .and_then(|n| p.get_available_node(&n.ty));
+                    SearchItem {
+                        kind: SearchItemKind::GraphNode,
+                        id: idx.to_string().into(),
+                        category: node
+                            .as_ref()
+                            .map(|n| n.category.as_str())
+                            .unwrap_or_default()
+                            .into(),
+                        description: format!("Node {}", idx).into(),
+                        name: p.node_title(idx).into(),
+                        shortcut: "".into(),
+                        inputs: "".into(),
+                        outputs: "".into(),
+                        tags: "".into(),
+                    }
+                }),
+                PaletteEntry::Bookmark(name) => project
+                    .and_then(|p| p.graph().bookmark(&name))
+                    .map(|bookmark| SearchItem {
+                        kind: SearchItemKind::Bookmark,
+                        id: name.as_str().into(),
+                        category: "Bookmark".into(),
+                        description: format!("Zoom {:.2}", bookmark.zoom).into(),
+                        name: name.as_str().into(),
+                        shortcut: bookmark
+                            .key
+                            .map(|k| k.to_string())
+                            .unwrap_or_default()
+                            .into(),
+                        inputs: "".into(),
+                        outputs: "".into(),
+                        tags: "".into(),
+                    }),
+                PaletteEntry::NewBookmark(name) => Some(SearchItem {
+                    kind: SearchItemKind::NewBookmark,
+                    id: name.as_str().into(),
+                    category: "Bookmark".into(),
+                    description: "Save the current view under this name".into(),
+                    name: name.as_str().into(),
+                    shortcut: "".into(),
+                    inputs: "".into(),
+                    outputs: "".into(),
+                    tags: "".into(),
+                }),
+                PaletteEntry::Template(name) => {
+                    model.templates().get(&name).map(|template| SearchItem {
+                        kind: if new_tab {
+                            SearchItemKind::TemplateTab
+                        } else {
+                            SearchItemKind::Template
+                        },
+                        id: name.as_str().into(),
+                        category: "Template".into(),
+                        description: template.description.as_str().into(),
+                        name: template.name.as_str().into(),
+                        shortcut: "".into(),
+                        inputs: "".into(),
+                        outputs: "".into(),
+                        tags: template.tags.join(", ").into(),
+                    })
+                }
             })
             .collect::<Vec<_>>();
         ui.set_command_palette_results(ModelRc::new(VecModel::from_slice(&res)))
     }
 }
+
+fn signature(slots: &[(String, LinkType)]) -> String {
+    slots
+        .iter()
+        .map(|(_, ty)| ty.0.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,14 +1,15 @@
-use self::{floating::Floating, links::Links, nodes::Nodes};
+use self::{floating::Floating, groups::Groups, links::Links, nodes::Nodes};
 use super::{Aro, Controller};
 use crate::{
     ctrl::Event,
     model::Model,
     ui::{GraphLogic, View},
 };
-use slint::ComponentHandle;
+use slint::{ComponentHandle, SharedString, VecModel};
 use std::sync::mpsc::Sender;
 
 mod floating;
+mod groups;
 mod links;
 mod nodes;
 
@@ -19,6 +20,7 @@ impl Controller for Graph {
         Nodes::setup(model.clone(), ui, tx.clone());
         Links::setup(model.clone(), ui, tx.clone());
         Floating::setup(model.clone(), ui, tx.clone());
+        Groups::setup(model.clone(), ui, tx.clone());
 
         let model = model.read();
         refresh(ui, &model);
@@ -35,6 +37,25 @@ impl Controller for Graph {
                 tx.send(Event::SetOffset(x, y)).unwrap();
             }
         });
+        ui.global::<GraphLogic>().on_select_rect({
+            let tx = tx.clone();
+            move |x, y, width, height, extend| {
+                tx.send(Event::SelectInRect((x, y, width, height), extend))
+                    .unwrap();
+            }
+        });
+        ui.global::<GraphLogic>().on_leave_subgraph({
+            let tx = tx.clone();
+            move |depth| {
+                tx.send(Event::LeaveSubgraph(depth as usize)).unwrap();
+            }
+        });
+        ui.global::<GraphLogic>().on_clear_selection({
+            let tx = tx.clone();
+            move || {
+                tx.send(Event::ClearSelection).unwrap();
+            }
+        });
     }
 
     fn notify(ui: &View, model: &Model, evt: &Event) {
@@ -42,24 +63,70 @@ impl Controller for Graph {
 
         use Event::*;
         match evt {
-            SetOffset(..) | SetZoom(..) | OpenFile | SetNodePosition(..) | CloseTab(..)
-            | SelectTab(..) | NewTab => {
+            SetOffset(..)
+            | SetZoom(..)
+            | OpenFile
+            | OpenPath(..)
+            | SetNodePosition(..)
+            | SetNoteText(..)
+            | CloseTab(..)
+            | SelectTab(..)
+            | NewTab
+            | Undo
+            | Redo
+            | ZoomToFit(..)
+            | ZoomToSelection(..)
+            | CenterNode(..)
+            | GoToBookmark(..)
+            | AutoLayout
+            | Align(..)
+            | Distribute(..)
+            | MoveGroup(..)
+            | CollapseSelection
+            | OpenSubgraph(..)
+            | LeaveSubgraph(..)
+            | NewTabFromTemplate(..)
+            | InsertTemplate(..) => {
+                Groups::notify(ui, model, evt);
+                Nodes::notify(ui, model, evt);
+                Links::notify(ui, model, evt);
+            }
+            GroupSelection | ResizeGroup(..) | RenameGroup(..) | CycleGroupColor(..)
+            | RemoveGroup(..) => {
+                Groups::notify(ui, model, evt);
+            }
+            AddLinkedNode(..) | InsertReroute(..) | PromptFinished(..) => {
                 Nodes::notify(ui, model, evt);
                 Links::notify(ui, model, evt);
             }
             RemoveLink(..) | AddLink(..) => {
                 Links::notify(ui, model, evt);
             }
-            AddNode(..) => {
+            AddNode(..) | SelectNode(..) | SelectInRect(..) | ClearSelection
+            | ToggleNodeMode(..) => {
                 Nodes::notify(ui, model, evt);
             }
-            Save | SaveAs | SetCommandSearch(..) => {}
+            Save | SaveAs | ExportWorkflow | ExportTimeline(..) | SetCommandSearch(..)
+            | ClearRecentFiles | SaveTemplate(..) | SaveBookmark(..) | ToggleSnapToGrid
+            | QueuePrompt(..) | AddSegment(..) | MoveSegment(..) | TrimSegment(..)
+            | RemoveSegment(..) | SetFps(..) | AddTrack | RemoveTrack(..) | MoveTrack(..)
+            | RenameTrack(..) | ToggleTrackMute(..) | ToggleTrackLock(..) | SetKeyframe(..)
+            | RemoveKeyframes(..) | RenderRange | RenderFinished(..) | PromptFailed(..)
+            | CancelRender | RenderQueued(..) | RenderProgress(..) => {}
         }
     }
 }
 
 fn refresh(ui: &View, model: &Model) {
     if let Some(project) = model.tabs().selected_project() {
+        // the top level is left out when no subgraph is open
+        let breadcrumb = project.breadcrumb();
+        let names = std::iter::once("Graph")
+            .chain(breadcrumb.iter().map(String::as_str))
+            .filter(|_| !breadcrumb.is_empty())
+            .map(SharedString::from)
+            .collect::<Vec<_>>();
+        ui.set_breadcrumb(VecModel::from_slice(&names));
         let graph = project.graph();
         ui.set_zoom(graph.zoom());
         let (x, y) = graph.offset();
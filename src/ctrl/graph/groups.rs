@@ -0,0 +1,92 @@
+use super::{Aro, Controller};
+use crate::{
+    ctrl::Event,
+    model::Model,
+    ui::{GroupData, GroupDrag, GroupLogic, View},
+};
+use slint::{Color, ComponentHandle, VecModel};
+use std::sync::mpsc::Sender;
+
+pub struct Groups;
+
+impl Controller for Groups {
+    fn setup(model: Aro<Model>, ui: &View, tx: Sender<Event>) {
+        ui.global::<GroupLogic>().on_move_group({
+            let tx = tx.clone();
+            move |idx, x, y| {
+                tx.send(Event::MoveGroup(idx as usize, x, y)).unwrap();
+            }
+        });
+        ui.global::<GroupLogic>().on_resize_group({
+            let tx = tx.clone();
+            move |idx, width, height| {
+                tx.send(Event::ResizeGroup(idx as usize, width, height))
+                    .unwrap();
+            }
+        });
+        ui.global::<GroupLogic>().on_rename_group({
+            let tx = tx.clone();
+            move |idx, title| {
+                tx.send(Event::RenameGroup(idx as usize, title.into()))
+                    .unwrap();
+            }
+        });
+        ui.global::<GroupLogic>().on_cycle_group_color({
+            let tx = tx.clone();
+            move |idx| {
+                tx.send(Event::CycleGroupColor(idx as usize)).unwrap();
+            }
+        });
+        ui.global::<GroupLogic>().on_remove_group({
+            move |idx| {
+                tx.send(Event::RemoveGroup(idx as usize)).unwrap();
+            }
+        });
+        let model = model.read();
+        refresh(ui, &model);
+    }
+
+    fn notify(ui: &View, model: &Model, _evt: &Event) {
+        refresh(ui, model);
+    }
+}
+
+fn refresh(ui: &View, model: &Model) {
+    if let Some(project) = model.tabs().selected_project() {
+        ui.set_groups(VecModel::from_slice(
+            &project
+                .graph()
+                .get_groups()
+                .iter()
+                .map(|g| GroupData {
+                    x: g.rect.0,
+                    y: g.rect.1,
+                    width: g.rect.2,
+                    height: g.rect.3,
+                    title: g.title.as_str().into(),
+                    color: parse_color(&g.color),
+                })
+                .collect::<Vec<_>>(),
+        ));
+        ui.set_group_drag(GroupDrag::default());
+    }
+}
+
+/// Parses `#rgb` and `#rrggbb` colours as written by ComfyUI.
+fn parse_color(color: &str) -> Color {
+    let hex = color.trim_start_matches('#');
+    let channel = |i: usize, len: usize| {
+        u8::from_str_radix(hex.get(i * len..(i + 1) * len)?, 16)
+            .ok()
+            .map(|v| if len == 1 { v * 17 } else { v })
+    };
+    let len = match hex.len() {
+        3 => 1,
+        6 => 2,
+        _ => return Color::from_rgb_u8(0x44, 0x44, 0x44),
+    };
+    match (channel(0, len), channel(1, len), channel(2, len)) {
+        (Some(r), Some(g), Some(b)) => Color::from_rgb_u8(r, g, b),
+        _ => Color::from_rgb_u8(0x44, 0x44, 0x44),
+    }
+}
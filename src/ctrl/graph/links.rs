@@ -46,6 +46,13 @@ fn refresh(ui: &View, model: &Model) {
 }
 
 fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
+    ui.global::<ui::LinkLogic>().on_insert_reroute({
+        let tx = tx.clone();
+        move |link_idx, x, y| {
+            tx.send(Event::InsertReroute(link_idx as usize, (x, y)))
+                .unwrap();
+        }
+    });
     ui.global::<ui::LinkLogic>().on_new_link_from_output({
         let ui = ui.as_weak();
         let tx = tx.clone();
@@ -54,11 +61,7 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
             let ui = ui.upgrade().unwrap();
             let model = model.read();
             if let Some(project) = model.tabs().selected_project() {
-                if let Some(slot_ty) = project
-                    .graph()
-                    .get_node(node_idx as usize)
-                    .and_then(|ni| project.get_available_node(&ni.ty))
-                    .map(|n| n.outputs[slot_idx as usize].1.clone())
+                if let Some(slot_ty) = project.slot_type(node_idx as usize, true, slot_idx as usize)
                 {
                     for (i, link) in project.graph().get_links().iter().enumerate() {
                         if link.src_node == node_idx as usize && link.src_slot == slot_idx as usize
@@ -67,7 +70,7 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
                                 floating_state: ui::FloatingState::DstAttached,
                                 node: link.dst_node as i32,
                                 node_slot: link.dst_slot as i32,
-                                ty: slot_ty.into(),
+                                ty: slot_ty.0.as_str().into(),
                                 x: 0.,
                                 y: 0.,
                             });
@@ -80,7 +83,7 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
                         floating_state: ui::FloatingState::SrcAttached,
                         node: node_idx,
                         node_slot: slot_idx,
-                        ty: slot_ty.into(),
+                        ty: slot_ty.0.as_str().into(),
                         x: 0.,
                         y: 0.,
                     });
@@ -96,11 +99,8 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
             let ui = ui.upgrade().unwrap();
             let model = model.read();
             if let Some(project) = model.tabs().selected_project() {
-                if let Some(slot_ty) = project
-                    .graph()
-                    .get_node(node_idx as usize)
-                    .and_then(|ni| project.get_available_node(&ni.ty))
-                    .map(|n| n.inputs[slot_idx as usize].1.clone())
+                if let Some(slot_ty) =
+                    project.slot_type(node_idx as usize, false, slot_idx as usize)
                 {
                     for (i, link) in project.graph().get_links().iter().enumerate() {
                         if link.dst_node == node_idx as usize && link.dst_slot == slot_idx as usize
@@ -110,7 +110,7 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
                                 floating_state: ui::FloatingState::SrcAttached,
                                 node: link.src_node as i32,
                                 node_slot: link.src_slot as i32,
-                                ty: slot_ty.into(),
+                                ty: slot_ty.0.as_str().into(),
                                 x: 0.,
                                 y: 0.,
                             });
@@ -123,7 +123,7 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
                         floating_state: ui::FloatingState::DstAttached,
                         node: node_idx,
                         node_slot: slot_idx,
-                        ty: slot_ty.into(),
+                        ty: slot_ty.0.as_str().into(),
                         x: 0.,
                         y: 0.,
                     });
@@ -139,14 +139,12 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
             let ui = ui.upgrade().unwrap();
             let model = model.read();
             if let Some(project) = model.tabs().selected_project() {
-                if let Some(slot_ty) = project
-                    .graph()
-                    .get_node(node_idx as usize)
-                    .and_then(|ni| project.get_available_node(&ni.ty))
-                    .map(|n| n.inputs[slot_idx as usize].1.clone())
+                if let Some(slot_ty) =
+                    project.slot_type(node_idx as usize, false, slot_idx as usize)
                 {
                     let floating = ui.get_floating();
-                    if floating.ty.as_str() == slot_ty.0
+                    let floating_ty = model::LinkType(floating.ty.as_str().into());
+                    if slot_ty.accepts(&floating_ty)
                         && floating.floating_state == ui::FloatingState::SrcAttached
                     {
                         tx.send(Event::AddLink(model::Link {
@@ -154,7 +152,7 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
                             dst_slot: slot_idx as usize,
                             src_node: floating.node as usize,
                             src_slot: floating.node_slot as usize,
-                            ty: slot_ty.clone(),
+                            ty: slot_ty.resolve(&floating_ty),
                         }))
                         .unwrap();
                     }
@@ -173,14 +171,11 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
             let ui = ui.upgrade().unwrap();
             let model = model.read();
             if let Some(project) = model.tabs().selected_project() {
-                if let Some(slot_ty) = project
-                    .graph()
-                    .get_node(node_idx as usize)
-                    .and_then(|ni| project.get_available_node(&ni.ty))
-                    .map(|n| n.outputs[slot_idx as usize].1.clone())
+                if let Some(slot_ty) = project.slot_type(node_idx as usize, true, slot_idx as usize)
                 {
                     let floating = ui.get_floating();
-                    if floating.ty.as_str() == slot_ty.0
+                    let floating_ty = model::LinkType(floating.ty.as_str().into());
+                    if slot_ty.accepts(&floating_ty)
                         && floating.floating_state == ui::FloatingState::DstAttached
                     {
                         tx.send(Event::AddLink(model::Link {
@@ -188,7 +183,7 @@ fn setup_link_logic(ui: &View, model: Aro<Model>, tx: Sender<Event>) {
                             src_slot: slot_idx as usize,
                             dst_node: floating.node as usize,
                             dst_slot: floating.node_slot as usize,
-                            ty: slot_ty.clone(),
+                            ty: slot_ty.resolve(&floating_ty),
                         }))
                         .unwrap();
                     }
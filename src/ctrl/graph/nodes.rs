@@ -1,16 +1,73 @@
 use super::{Aro, Controller};
 use crate::{
     ctrl::Event,
-    model::Model,
-    ui::{self, NodeData, Slot, View},
+    model::{self, Model, Segment},
+    ui::{self, Bounds, NodeData, NoteLine, NoteSpan, Slot, View},
 };
-use slint::{ComponentHandle, VecModel};
-use std::sync::mpsc::Sender;
+use slint::{Color, ComponentHandle, Image, VecModel};
+use std::{cell::RefCell, collections::HashMap, sync::mpsc::Sender};
+
+/// Colours told apart in the minimap, picked by hashing the node category.
+const CATEGORY_COLORS: &[u32] = &[
+    0x3f789e, 0xb06634, 0x5a9e5a, 0x8f6fb5, 0xb58b2a, 0x4f9e9e, 0xa1309b, 0x9e4f4f,
+];
+
+thread_local! {
+    /// Decoded previews by path. Nodes are refreshed on every drag step,
+    /// decoding their images each time would make that crawl.
+    static PREVIEWS: RefCell<HashMap<String, Option<Image>>> = RefCell::default();
+}
 
 pub struct Nodes;
 
 impl Controller for Nodes {
     fn setup(model: Aro<Model>, ui: &View, tx: Sender<Event>) {
+        ui.global::<ui::NodeLogic>().on_select_node({
+            let tx = tx.clone();
+            move |node_idx, extend| {
+                tx.send(Event::SelectNode(node_idx as usize, extend))
+                    .unwrap();
+            }
+        });
+        ui.global::<ui::NodeLogic>().on_set_note_text({
+            let tx = tx.clone();
+            move |node_idx, text| {
+                tx.send(Event::SetNoteText(node_idx as usize, text.into()))
+                    .unwrap();
+            }
+        });
+        ui.global::<ui::NodeLogic>().on_open_subgraph({
+            let tx = tx.clone();
+            move |node_idx| {
+                tx.send(Event::OpenSubgraph(node_idx as usize)).unwrap();
+            }
+        });
+        ui.global::<ui::NodeLogic>().on_drop_output({
+            let ui = ui.as_weak();
+            let model = model.clone();
+            let tx = tx.clone();
+            move |node_idx, x, y| {
+                let ui = ui.upgrade().unwrap();
+                let start = ui.invoke_timeline_time_at(x, y);
+                if start < 0. {
+                    return;
+                }
+                let model = model.read();
+                let Some(project) = model.tabs().selected_project() else {
+                    return;
+                };
+                if let Some(node) = project.graph().get_node(node_idx as usize) {
+                    let title = project.node_title(node_idx as usize);
+                    tx.send(Event::AddSegment(Segment::new(
+                        ui.invoke_timeline_track_at(y) as usize,
+                        start,
+                        title,
+                        node.outputs.clone(),
+                    )))
+                    .unwrap();
+                }
+            }
+        });
         ui.global::<ui::NodeLogic>().on_move_node({
             move |node_idx, x, y| {
                 tx.send(Event::SetNodePosition(node_idx as usize, x, y))
@@ -28,39 +85,122 @@ impl Controller for Nodes {
 
 fn refresh(ui: &View, model: &Model) {
     if let Some(project) = model.tabs().selected_project() {
+        let graph = project.graph();
+        // forget the images no node shows anymore
+        PREVIEWS.with_borrow_mut(|previews| {
+            previews.retain(|path, _| {
+                graph
+                    .get_nodes()
+                    .iter()
+                    .any(|n| n.outputs.first() == Some(path))
+            })
+        });
         ui.set_nodes(VecModel::from_slice(
-            &project
-                .graph()
+            &graph
                 .get_nodes()
                 .iter()
-                .map(|ni| {
-                    let n = project.get_available_node(&ni.ty).unwrap();
-                    NodeData {
+                .enumerate()
+                .filter_map(|(idx, ni)| {
+                    let n = project.node_info(idx)?;
+                    let (_, _, width, height) = project.node_rect(idx)?;
+                    let preview = ni.outputs.first().and_then(|path| preview(path));
+                    Some(NodeData {
                         inputs: VecModel::from_slice(
                             &n.inputs
                                 .iter()
-                                .map(|(name, ty)| Slot {
+                                .enumerate()
+                                .map(|(slot, (name, ty))| Slot {
                                     name: name.clone().into(),
-                                    ty: ty.clone().into(),
+                                    ty: project
+                                        .slot_type(idx, false, slot)
+                                        .unwrap_or_else(|| ty.clone())
+                                        .0
+                                        .into(),
                                 })
                                 .collect::<Vec<_>>(),
                         ),
                         outputs: VecModel::from_slice(
                             &n.outputs
                                 .iter()
-                                .map(|(name, ty)| Slot {
+                                .enumerate()
+                                .map(|(slot, (name, ty))| Slot {
                                     name: name.clone().into(),
-                                    ty: ty.clone().into(),
+                                    ty: project
+                                        .slot_type(idx, true, slot)
+                                        .unwrap_or_else(|| ty.clone())
+                                        .0
+                                        .into(),
                                 })
                                 .collect::<Vec<_>>(),
                         ),
                         text: n.name.clone().into(),
-                        width: 100.,
+                        reroute: ni.ty.is_reroute(),
+                        subgraph: ni.ty.subgraph_name().is_some(),
+                        mode: match ni.mode {
+                            model::NodeMode::Active => ui::NodeMode::Active,
+                            model::NodeMode::Muted => ui::NodeMode::Muted,
+                            model::NodeMode::Bypassed => ui::NodeMode::Bypassed,
+                        },
+                        note: ni.ty.is_note(),
+                        missing: project.is_missing_type(idx),
+                        body: ni.text.as_str().into(),
+                        lines: VecModel::from_slice(
+                            &model::parse_markdown(&ni.text)
+                                .into_iter()
+                                .map(|line| NoteLine {
+                                    heading: line.heading,
+                                    spans: VecModel::from_slice(
+                                        &line
+                                            .spans
+                                            .into_iter()
+                                            .map(|span| NoteSpan {
+                                                text: span.text.into(),
+                                                bold: span.bold,
+                                                italic: span.italic,
+                                            })
+                                            .collect::<Vec<_>>(),
+                                    ),
+                                })
+                                .collect::<Vec<_>>(),
+                        ),
+                        color: category_color(&n.category),
+                        has_preview: preview.is_some(),
+                        preview: preview.unwrap_or_default(),
+                        frames: ni.outputs.len() as i32,
+                        width,
+                        height,
+                        selected: graph.is_selected(idx),
                         x: ni.pos.0,
                         y: ni.pos.1,
-                    }
+                    })
                 })
                 .collect::<Vec<_>>(),
-        ))
+        ));
+        let (x, y, width, height) = project.bounds().unwrap_or_default();
+        ui.set_bounds(Bounds {
+            x,
+            y,
+            width,
+            height,
+        });
     }
 }
+
+/// A missing file just leaves the node without a preview.
+fn preview(path: &str) -> Option<Image> {
+    PREVIEWS.with_borrow_mut(|previews| {
+        previews
+            .entry(path.to_owned())
+            .or_insert_with(|| Image::load_from_path(path.as_ref()).ok())
+            .clone()
+    })
+}
+
+fn category_color(category: &str) -> Color {
+    // FNV-1a, stable across runs unlike the std hasher
+    let hash = category.bytes().fold(0x811c9dc5u32, |h, b| {
+        (h ^ b as u32).wrapping_mul(0x01000193)
+    });
+    let rgb = CATEGORY_COLORS[hash as usize % CATEGORY_COLORS.len()];
+    Color::from_rgb_u8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
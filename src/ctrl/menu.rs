@@ -1,21 +1,31 @@
 use super::{Controller, Event};
 use crate::{
     model::Model,
-    ui::{MenuLogic, View},
+    ui::{MenuLogic, RecentFile, View},
     utils::Aro,
 };
-use slint::ComponentHandle;
-use std::sync::mpsc::Sender;
+use slint::{ComponentHandle, VecModel};
+use std::{path::Path, sync::mpsc::Sender};
 
 pub struct Menu;
 
 impl Controller for Menu {
-    fn setup(_model: Aro<Model>, ui: &View, tx: Sender<Event>) {
-        // let model = model.read();
+    fn setup(model: Aro<Model>, ui: &View, tx: Sender<Event>) {
+        let model = model.read();
+        refresh(&model, ui);
+
         ui.global::<MenuLogic>().on_open_file({
             let tx = tx.clone();
             move || tx.send(Event::OpenFile).unwrap()
         });
+        ui.global::<MenuLogic>().on_open_recent({
+            let tx = tx.clone();
+            move |path| tx.send(Event::OpenPath(path.into())).unwrap()
+        });
+        ui.global::<MenuLogic>().on_clear_recent({
+            let tx = tx.clone();
+            move || tx.send(Event::ClearRecentFiles).unwrap()
+        });
         ui.global::<MenuLogic>().on_save({
             let tx = tx.clone();
             move || tx.send(Event::Save).unwrap()
@@ -24,6 +34,30 @@ impl Controller for Menu {
             let tx = tx.clone();
             move || tx.send(Event::SaveAs).unwrap()
         });
+        ui.global::<MenuLogic>().on_export_workflow({
+            let tx = tx.clone();
+            move || tx.send(Event::ExportWorkflow).unwrap()
+        });
+        ui.global::<MenuLogic>().on_export_timeline({
+            let ui = ui.as_weak();
+            move || ui.upgrade().unwrap().invoke_show_export_dialog()
+        });
+    }
+    fn notify(ui: &View, model: &Model, _evt: &Event) {
+        refresh(model, ui);
     }
-    fn notify(_ui: &View, _model: &Model, _evt: &Event) {}
+}
+
+fn refresh(model: &Model, ui: &View) {
+    let recent_files = model
+        .recent_files()
+        .paths()
+        .iter()
+        .map(|path| RecentFile {
+            path: path.into(),
+            exists: Path::new(path).exists(),
+        })
+        .collect::<Vec<_>>();
+
+    ui.set_recent_files(VecModel::from_slice(&recent_files));
 }
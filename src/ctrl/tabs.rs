@@ -3,6 +3,9 @@ use crate::{
     model::Model,
     ui::{TabLogic, View},
 };
+use i_slint_backend_winit::{
+    winit::event::WindowEvent, WinitWindowAccessor, WinitWindowEventResult,
+};
 use slint::{ComponentHandle, SharedString, VecModel};
 use std::{path::Path, sync::mpsc::Sender};
 
@@ -31,6 +34,19 @@ impl Controller for Tabs {
                 tx.send(Event::CloseTab(closing as usize)).unwrap();
             }
         });
+        // files dropped on the window open in a tab of their own
+        ui.window().on_winit_window_event({
+            let tx = tx.clone();
+            move |_, evt| match evt {
+                WindowEvent::DroppedFile(path) => {
+                    if let Some(path) = path.to_str() {
+                        tx.send(Event::OpenPath(path.into())).unwrap();
+                    }
+                    WinitWindowEventResult::PreventDefault
+                }
+                _ => WinitWindowEventResult::Propagate,
+            }
+        });
     }
     fn notify(ui: &View, model: &Model, _evt: &Event) {
         refresh(model, ui);
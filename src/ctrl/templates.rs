@@ -0,0 +1,32 @@
+use super::{Aro, Controller, Event};
+use crate::{
+    model::Model,
+    ui::{TemplateLogic, View},
+};
+use slint::ComponentHandle;
+use std::sync::mpsc::Sender;
+
+pub struct Templates;
+
+impl Controller for Templates {
+    fn setup(_model: Aro<Model>, ui: &View, tx: Sender<Event>) {
+        ui.global::<TemplateLogic>().on_save({
+            move |name, description, tags| {
+                let tags = tags
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+                tx.send(Event::SaveTemplate(
+                    name.trim().into(),
+                    description.trim().into(),
+                    tags,
+                ))
+                .unwrap();
+            }
+        });
+    }
+
+    fn notify(_ui: &View, _model: &Model, _evt: &Event) {}
+}
@@ -0,0 +1,261 @@
+use super::{Aro, Controller, Event};
+use crate::{
+    model::{ExportFormat, ExportOptions, Interpolation, Model, Segment},
+    ui::{ExportLogic, KeyframeLogic, SegmentData, TimelineLogic, TrackData, View},
+};
+use slint::{ComponentHandle, Image, ModelRc, VecModel};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::mpsc::Sender,
+};
+
+thread_local! {
+    /// Decoded segment images by path, sequences repeat theirs over many
+    /// frames and every timeline edit refreshes all of them.
+    static IMAGES: RefCell<HashMap<String, Option<Image>>> = RefCell::default();
+}
+
+pub struct Timeline;
+
+impl Controller for Timeline {
+    fn setup(model: Aro<Model>, ui: &View, tx: Sender<Event>) {
+        refresh(&model.read(), ui);
+
+        ui.global::<TimelineLogic>().on_add_segment({
+            let tx = tx.clone();
+            move || {
+                let model = model.read();
+                if let Some(project) = model.tabs().selected_project() {
+                    let timeline = project.timeline();
+                    let title = format!("Segment {}", timeline.segments().len() + 1);
+                    let track = timeline
+                        .tracks()
+                        .iter()
+                        .position(|t| !t.locked)
+                        .unwrap_or_default();
+                    tx.send(Event::AddSegment(Segment::new(
+                        track,
+                        timeline.end(),
+                        title,
+                        vec![],
+                    )))
+                    .unwrap();
+                }
+            }
+        });
+        ui.global::<TimelineLogic>().on_move_segment({
+            let tx = tx.clone();
+            move |idx, start, track| {
+                tx.send(Event::MoveSegment(idx as usize, start, track as usize))
+                    .unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_trim_segment({
+            let tx = tx.clone();
+            move |idx, start, duration| {
+                tx.send(Event::TrimSegment(idx as usize, start, duration))
+                    .unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_set_fps({
+            let tx = tx.clone();
+            move |fps| {
+                tx.send(Event::SetFps(fps as f32)).unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_remove_segment({
+            let tx = tx.clone();
+            move |idx| {
+                tx.send(Event::RemoveSegment(idx as usize)).unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_add_track({
+            let tx = tx.clone();
+            move || {
+                tx.send(Event::AddTrack).unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_remove_track({
+            let tx = tx.clone();
+            move |idx| {
+                tx.send(Event::RemoveTrack(idx as usize)).unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_move_track({
+            let tx = tx.clone();
+            move |from, to| {
+                tx.send(Event::MoveTrack(from as usize, to as usize))
+                    .unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_rename_track({
+            let tx = tx.clone();
+            move |idx, name| {
+                tx.send(Event::RenameTrack(idx as usize, name.trim().into()))
+                    .unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_toggle_track_mute({
+            let tx = tx.clone();
+            move |idx| {
+                tx.send(Event::ToggleTrackMute(idx as usize)).unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_toggle_track_lock({
+            let tx = tx.clone();
+            move |idx| {
+                tx.send(Event::ToggleTrackLock(idx as usize)).unwrap();
+            }
+        });
+        ui.global::<TimelineLogic>().on_cancel_render({
+            let tx = tx.clone();
+            move || {
+                tx.send(Event::CancelRender).unwrap();
+            }
+        });
+        ui.global::<ExportLogic>().on_export({
+            let tx = tx.clone();
+            move |format, fps, width, height| {
+                let Some(format) = ExportFormat::from_name(&format) else {
+                    return;
+                };
+                // the images keep their size unless both sides are given
+                let size = width.trim().parse().ok().zip(height.trim().parse().ok());
+                tx.send(Event::ExportTimeline(ExportOptions {
+                    format,
+                    fps: fps as f32,
+                    size: size.filter(|&(w, h)| w > 0 && h > 0),
+                }))
+                .unwrap();
+            }
+        });
+        ui.global::<KeyframeLogic>().on_save({
+            let ui = ui.as_weak();
+            move |widget, value, interpolation| {
+                let ui = ui.upgrade().unwrap();
+                // numbers are keyed as numbers so they can be interpolated
+                let value = serde_json::from_str::<serde_json::Value>(value.trim())
+                    .ok()
+                    .filter(|v| v.is_number())
+                    .unwrap_or_else(|| value.as_str().into());
+                tx.send(Event::SetKeyframe(
+                    widget.trim().into(),
+                    ui.get_playhead(),
+                    value,
+                    Interpolation::from_name(&interpolation).unwrap_or_default(),
+                ))
+                .unwrap();
+            }
+        });
+    }
+    fn notify(ui: &View, model: &Model, evt: &Event) {
+        use Event::*;
+        match evt {
+            SelectTab(..)
+            | NewTab
+            | NewTabFromTemplate(..)
+            | CloseTab(..)
+            | OpenFile
+            | OpenPath(..)
+            | Undo
+            | Redo
+            | AddSegment(..)
+            | MoveSegment(..)
+            | TrimSegment(..)
+            | RemoveSegment(..)
+            | SetFps(..)
+            | AddTrack
+            | RemoveTrack(..)
+            | MoveTrack(..)
+            | RenameTrack(..)
+            | ToggleTrackMute(..)
+            | ToggleTrackLock(..)
+            | RenderFinished(..) => {
+                refresh(model, ui);
+            }
+            SetKeyframe(..) | RemoveKeyframes(..) | InsertTemplate(..) => {
+                refresh_keyframes(model, ui);
+            }
+            RenderRange | RenderProgress(..) | PromptFailed(..) => {
+                refresh_render(model, ui);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn refresh(model: &Model, ui: &View) {
+    // the timeline is hidden without a project
+    let Some(project) = model.tabs().selected_project() else {
+        return;
+    };
+    let timeline = project.timeline();
+    // forget the images no segment shows anymore
+    let used = timeline
+        .segments()
+        .iter()
+        .flat_map(|s| &s.frames)
+        .collect::<HashSet<_>>();
+    IMAGES.with_borrow_mut(|images| images.retain(|path, _| used.contains(path)));
+    let segments = timeline
+        .segments()
+        .iter()
+        .map(|segment| {
+            let preview = segment.preview().and_then(load);
+            let track = timeline.tracks().get(segment.track);
+            SegmentData {
+                track: segment.track as i32,
+                muted: track.is_some_and(|t| t.muted),
+                locked: track.is_some_and(|t| t.locked),
+                start: segment.start,
+                duration: segment.duration,
+                title: segment.title.as_str().into(),
+                has_preview: preview.is_some(),
+                preview: preview.unwrap_or_default(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let frames = (0..timeline.frame_count())
+        .map(|frame| timeline.image_at(frame).and_then(load).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let tracks = timeline
+        .tracks()
+        .iter()
+        .map(|track| TrackData {
+            name: track.name.as_str().into(),
+            muted: track.muted,
+            locked: track.locked,
+        })
+        .collect::<Vec<_>>();
+    ui.set_segments(ModelRc::new(VecModel::from(segments)));
+    ui.set_tracks(ModelRc::new(VecModel::from(tracks)));
+    ui.set_timeline_frames(ModelRc::new(VecModel::from(frames)));
+    ui.set_timeline_fps(timeline.fps());
+    ui.set_timeline_end(timeline.end());
+    refresh_keyframes(model, ui);
+    refresh_render(model, ui);
+}
+
+fn refresh_keyframes(model: &Model, ui: &View) {
+    if let Some(project) = model.tabs().selected_project() {
+        ui.set_keyframe_times(ModelRc::new(VecModel::from(project.keyframe_times())));
+    }
+}
+
+fn refresh_render(model: &Model, ui: &View) {
+    let render = model.tabs().selected_project().and_then(|p| p.render());
+    let (done, total) = render.map_or((0, 0), |r| (r.done, r.total));
+    ui.set_render_done(done as i32);
+    ui.set_render_total(total as i32);
+}
+
+/// A missing file just leaves the segment or frame without a picture.
+fn load(path: &str) -> Option<Image> {
+    IMAGES.with_borrow_mut(|images| {
+        images
+            .entry(path.to_owned())
+            .or_insert_with(|| Image::load_from_path(path.as_ref()).ok())
+            .clone()
+    })
+}
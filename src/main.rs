@@ -1,3 +1,4 @@
+mod cli;
 mod ctrl;
 mod model;
 mod utils;
@@ -11,10 +12,17 @@ use slint::ComponentHandle;
 use ui::View;
 
 fn main() -> Result<(), slint::PlatformError> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    // subcommands run headless, before anything of the UI is set up
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     let model = Model::new();
+    let paths = args;
 
     let ui = View::new()?;
-    let ctrl = Mediator::new(&ui, model);
+    let ctrl = Mediator::new(&ui, model, paths);
 
     std::thread::spawn(move || {
         ctrl.run();
@@ -1,24 +1,38 @@
 pub use self::{
-    backend::Backend,
-    tabs::{
-        project::{
-            graph::Graph,
-            graph::{Link, LinkType, NodeType},
-            Node,
-        },
-        Tabs,
-    },
+    command::{Command, PaletteEntry, Shortcut},
+    markdown::parse as parse_markdown,
+    query::PaletteQuery,
+    recent::RecentFiles,
+    settings::Settings,
+    tabs::{project::Project, Tabs},
+    templates::{Template, Templates},
 };
+pub use kira_core::{
+    align::{Alignment, Axis},
+    export_timeline, save_project, Backend, DanglingLink, ExportFormat, ExportOptions, Graph,
+    Interpolation, Link, LinkType, Node, NodeMode, NodeType, Segment,
+};
+
+use simsearch::SimSearch;
+use std::collections::HashMap;
 
-mod backend;
-mod file;
+mod command;
+mod markdown;
+mod query;
+mod recent;
+mod settings;
 mod tabs;
+mod templates;
 
 #[derive(Debug)]
 pub struct Model {
     tabs: Tabs,
     backend: Backend,
     command_search: String,
+    command_index: SimSearch<PaletteEntry>,
+    recent_files: RecentFiles,
+    templates: Templates,
+    settings: Settings,
 }
 
 impl Model {
@@ -27,6 +41,10 @@ impl Model {
             tabs: Tabs::new(),
             backend: Backend::new(),
             command_search: "".into(),
+            command_index: command::palette_index(),
+            recent_files: RecentFiles::load(),
+            templates: Templates::load(),
+            settings: Settings::load(),
         }
     }
     pub fn backend(&self) -> &Backend {
@@ -41,7 +59,35 @@ impl Model {
     pub fn command_search(&self) -> &String {
         &self.command_search
     }
+    pub fn recent_files(&self) -> &RecentFiles {
+        &self.recent_files
+    }
+    pub fn recent_files_mut(&mut self) -> &mut RecentFiles {
+        &mut self.recent_files
+    }
+    pub fn templates(&self) -> &Templates {
+        &self.templates
+    }
+    pub fn templates_mut(&mut self) -> &mut Templates {
+        &mut self.templates
+    }
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
     pub fn set_command_search(&mut self, query: String) {
         self.command_search = query;
     }
+    /// Commands and templates stay searchable even when no project is open.
+    pub fn search_palette(&self, query: &str) -> Vec<PaletteEntry> {
+        let query = PaletteQuery::parse(query);
+        let mut entries = match self.tabs.selected_project() {
+            Some(project) => project.search_palette(&query),
+            None => query.search(&self.command_index, &HashMap::new()),
+        };
+        entries.extend(query.search_templates(&self.templates));
+        entries
+    }
 }
@@ -0,0 +1,385 @@
+use super::NodeType;
+use simsearch::{SearchOptions, SimSearch};
+use std::fmt;
+
+/// Every action the user can trigger, whether from a menu, a controller or
+/// the command palette.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Command {
+    OpenFile,
+    Save,
+    SaveAs,
+    ExportWorkflow,
+    ExportTimeline,
+    NewTab,
+    NewTabFromTemplate,
+    SaveAsTemplate,
+    CloseTab,
+    ClearRecentFiles,
+    Undo,
+    Redo,
+    ZoomToFit,
+    ZoomToSelection,
+    GoToNode,
+    AddBookmark,
+    GoToBookmark,
+    AutoLayout,
+    AlignLeft,
+    AlignTop,
+    AlignCenter,
+    AlignMiddle,
+    DistributeHorizontally,
+    DistributeVertically,
+    ToggleSnapToGrid,
+    ToggleMute,
+    ToggleBypass,
+    GroupSelection,
+    CollapseToSubgraph,
+    InsertTemplate,
+    TogglePlayback,
+    StopPlayback,
+    AddKeyframe,
+    RemoveKeyframe,
+    QueuePrompt,
+    RenderRange,
+}
+
+impl Command {
+    pub const ALL: &'static [Command] = &[
+        Command::OpenFile,
+        Command::Save,
+        Command::SaveAs,
+        Command::ExportWorkflow,
+        Command::ExportTimeline,
+        Command::NewTab,
+        Command::NewTabFromTemplate,
+        Command::SaveAsTemplate,
+        Command::CloseTab,
+        Command::ClearRecentFiles,
+        Command::Undo,
+        Command::Redo,
+        Command::ZoomToFit,
+        Command::ZoomToSelection,
+        Command::GoToNode,
+        Command::AddBookmark,
+        Command::GoToBookmark,
+        Command::AutoLayout,
+        Command::AlignLeft,
+        Command::AlignTop,
+        Command::AlignCenter,
+        Command::AlignMiddle,
+        Command::DistributeHorizontally,
+        Command::DistributeVertically,
+        Command::ToggleSnapToGrid,
+        Command::ToggleMute,
+        Command::ToggleBypass,
+        Command::GroupSelection,
+        Command::CollapseToSubgraph,
+        Command::InsertTemplate,
+        Command::TogglePlayback,
+        Command::StopPlayback,
+        Command::AddKeyframe,
+        Command::RemoveKeyframe,
+        Command::QueuePrompt,
+        Command::RenderRange,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Command::OpenFile => "open_file",
+            Command::Save => "save",
+            Command::SaveAs => "save_as",
+            Command::ExportWorkflow => "export_workflow",
+            Command::ExportTimeline => "export_timeline",
+            Command::NewTab => "new_tab",
+            Command::NewTabFromTemplate => "new_tab_from_template",
+            Command::SaveAsTemplate => "save_as_template",
+            Command::CloseTab => "close_tab",
+            Command::ClearRecentFiles => "clear_recent_files",
+            Command::Undo => "undo",
+            Command::Redo => "redo",
+            Command::ZoomToFit => "zoom_to_fit",
+            Command::ZoomToSelection => "zoom_to_selection",
+            Command::GoToNode => "go_to_node",
+            Command::AddBookmark => "add_bookmark",
+            Command::GoToBookmark => "go_to_bookmark",
+            Command::AutoLayout => "auto_layout",
+            Command::AlignLeft => "align_left",
+            Command::AlignTop => "align_top",
+            Command::AlignCenter => "align_center",
+            Command::AlignMiddle => "align_middle",
+            Command::DistributeHorizontally => "distribute_horizontally",
+            Command::DistributeVertically => "distribute_vertically",
+            Command::ToggleSnapToGrid => "toggle_snap_to_grid",
+            Command::ToggleMute => "toggle_mute",
+            Command::ToggleBypass => "toggle_bypass",
+            Command::GroupSelection => "group_selection",
+            Command::CollapseToSubgraph => "collapse_to_subgraph",
+            Command::InsertTemplate => "insert_template",
+            Command::TogglePlayback => "toggle_playback",
+            Command::StopPlayback => "stop_playback",
+            Command::AddKeyframe => "add_keyframe",
+            Command::RemoveKeyframe => "remove_keyframe",
+            Command::QueuePrompt => "queue_prompt",
+            Command::RenderRange => "render_range",
+        }
+    }
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.id() == id)
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::OpenFile => "Open File",
+            Command::Save => "Save",
+            Command::SaveAs => "Save As",
+            Command::ExportWorkflow => "Export Workflow",
+            Command::ExportTimeline => "Export Timeline",
+            Command::NewTab => "New Tab",
+            Command::NewTabFromTemplate => "New Tab from Template",
+            Command::SaveAsTemplate => "Save as Template",
+            Command::CloseTab => "Close Tab",
+            Command::ClearRecentFiles => "Clear Recent Files",
+            Command::Undo => "Undo",
+            Command::Redo => "Redo",
+            Command::ZoomToFit => "Zoom to Fit",
+            Command::ZoomToSelection => "Zoom to Selection",
+            Command::GoToNode => "Go to Node",
+            Command::AddBookmark => "Add Bookmark",
+            Command::GoToBookmark => "Go to Bookmark",
+            Command::AutoLayout => "Auto Layout",
+            Command::AlignLeft => "Align Left",
+            Command::AlignTop => "Align Top",
+            Command::AlignCenter => "Align Centres",
+            Command::AlignMiddle => "Align Middles",
+            Command::DistributeHorizontally => "Distribute Horizontally",
+            Command::DistributeVertically => "Distribute Vertically",
+            Command::ToggleSnapToGrid => "Toggle Snap to Grid",
+            Command::ToggleMute => "Mute / Unmute",
+            Command::ToggleBypass => "Bypass / Unbypass",
+            Command::GroupSelection => "Group Selection",
+            Command::CollapseToSubgraph => "Collapse to Subgraph",
+            Command::InsertTemplate => "Insert Template",
+            Command::TogglePlayback => "Play/Pause",
+            Command::StopPlayback => "Stop",
+            Command::AddKeyframe => "Add Keyframe",
+            Command::RemoveKeyframe => "Remove Keyframe",
+            Command::QueuePrompt => "Queue Prompt",
+            Command::RenderRange => "Render Range",
+        }
+    }
+    pub fn description(&self) -> &'static str {
+        match self {
+            Command::OpenFile => "Open a graph, workflow or image in a new tab",
+            Command::Save => "Save the current graph",
+            Command::SaveAs => "Save the current graph to a new file",
+            Command::ExportWorkflow => "Save the current graph as a ComfyUI workflow",
+            Command::ExportTimeline => {
+                "Write the timeline as frames, an animated image or an ffmpeg script"
+            }
+            Command::NewTab => "Open an empty graph",
+            Command::NewTabFromTemplate => "Open a new graph starting from a saved template",
+            Command::SaveAsTemplate => {
+                "Save the selected nodes, or the whole graph, to the template library"
+            }
+            Command::CloseTab => "Close the current graph",
+            Command::ClearRecentFiles => "Forget the recently opened files",
+            Command::Undo => "Revert the last change to the graph",
+            Command::Redo => "Reapply the last reverted change",
+            Command::ZoomToFit => "Fit the whole graph into view",
+            Command::ZoomToSelection => "Fit the selected nodes into view",
+            Command::GoToNode => "Find a node of the graph by name and centre it",
+            Command::AddBookmark => {
+                "Save the current view under a name, recalled with its number key"
+            }
+            Command::GoToBookmark => "Return to a saved view",
+            Command::AutoLayout => "Arrange the selected nodes, or the whole graph, in columns",
+            Command::AlignLeft => "Line the selected nodes up on their left edges",
+            Command::AlignTop => "Line the selected nodes up on their top edges",
+            Command::AlignCenter => "Centre the selected nodes on a vertical line",
+            Command::AlignMiddle => "Centre the selected nodes on a horizontal line",
+            Command::DistributeHorizontally => {
+                "Leave the same horizontal gap between the selected nodes"
+            }
+            Command::DistributeVertically => {
+                "Leave the same vertical gap between the selected nodes"
+            }
+            Command::ToggleSnapToGrid => "Snap moved nodes to a grid",
+            Command::ToggleMute => "Leave the selected nodes out of execution",
+            Command::ToggleBypass => {
+                "Skip the selected nodes, passing their inputs on to their outputs"
+            }
+            Command::GroupSelection => "Frame the selected nodes in a titled group",
+            Command::CollapseToSubgraph => {
+                "Replace the selected nodes with a reusable subgraph node"
+            }
+            Command::InsertTemplate => "Add the nodes of a saved template to the graph",
+            Command::TogglePlayback => "Start or pause playing the timeline",
+            Command::StopPlayback => "Stop playing and go back to the start of the timeline",
+            Command::AddKeyframe => "Key a widget of the selected nodes to a value at the playhead",
+            Command::RemoveKeyframe => "Remove the keyframes of the selected nodes at the playhead",
+            Command::QueuePrompt => "Send the graph to the backend for execution",
+            Command::RenderRange => {
+                "Queue a prompt for every frame between the first and last keyframe"
+            }
+        }
+    }
+    pub fn category(&self) -> &'static str {
+        match self {
+            Command::OpenFile
+            | Command::Save
+            | Command::SaveAs
+            | Command::ExportWorkflow
+            | Command::ExportTimeline
+            | Command::NewTab
+            | Command::NewTabFromTemplate
+            | Command::SaveAsTemplate
+            | Command::CloseTab
+            | Command::ClearRecentFiles => "File",
+            Command::Undo
+            | Command::Redo
+            | Command::AutoLayout
+            | Command::AlignLeft
+            | Command::AlignTop
+            | Command::AlignCenter
+            | Command::AlignMiddle
+            | Command::DistributeHorizontally
+            | Command::DistributeVertically
+            | Command::ToggleSnapToGrid
+            | Command::ToggleMute
+            | Command::ToggleBypass
+            | Command::GroupSelection
+            | Command::CollapseToSubgraph
+            | Command::InsertTemplate => "Edit",
+            Command::ZoomToFit
+            | Command::ZoomToSelection
+            | Command::GoToNode
+            | Command::AddBookmark
+            | Command::GoToBookmark => "View",
+            Command::TogglePlayback
+            | Command::StopPlayback
+            | Command::AddKeyframe
+            | Command::RemoveKeyframe => "Timeline",
+            Command::QueuePrompt | Command::RenderRange => "Backend",
+        }
+    }
+    pub fn shortcut(&self) -> Option<Shortcut> {
+        match self {
+            Command::OpenFile => Some(Shortcut::ctrl("o")),
+            Command::Save => Some(Shortcut::ctrl("s")),
+            Command::SaveAs => Some(Shortcut::ctrl_shift("s")),
+            Command::ExportWorkflow => None,
+            Command::ExportTimeline => None,
+            Command::NewTab => Some(Shortcut::ctrl("t")),
+            Command::NewTabFromTemplate => Some(Shortcut::ctrl_shift("t")),
+            Command::SaveAsTemplate => None,
+            Command::CloseTab => Some(Shortcut::ctrl("w")),
+            Command::ClearRecentFiles => None,
+            Command::Undo => Some(Shortcut::ctrl("z")),
+            Command::Redo => Some(Shortcut::ctrl_shift("z")),
+            Command::ZoomToFit => Some(Shortcut::ctrl("0")),
+            Command::ZoomToSelection => Some(Shortcut::ctrl_shift("f")),
+            Command::GoToNode => Some(Shortcut::ctrl("f")),
+            Command::AddBookmark => Some(Shortcut::ctrl("b")),
+            Command::GoToBookmark => Some(Shortcut::ctrl_shift("b")),
+            Command::AutoLayout => Some(Shortcut::ctrl("l")),
+            Command::AlignLeft
+            | Command::AlignTop
+            | Command::AlignCenter
+            | Command::AlignMiddle
+            | Command::DistributeHorizontally
+            | Command::DistributeVertically
+            | Command::ToggleSnapToGrid => None,
+            Command::ToggleMute => Some(Shortcut::ctrl("m")),
+            Command::ToggleBypass => Some(Shortcut::ctrl_shift("m")),
+            Command::GroupSelection => Some(Shortcut::ctrl("g")),
+            Command::CollapseToSubgraph => Some(Shortcut::ctrl_shift("g")),
+            Command::InsertTemplate => None,
+            Command::TogglePlayback => Some(Shortcut::new(" ", false, false, false)),
+            Command::StopPlayback => None,
+            Command::AddKeyframe => Some(Shortcut::new("k", false, false, false)),
+            Command::RemoveKeyframe => Some(Shortcut::new("k", false, false, true)),
+            Command::QueuePrompt => Some(Shortcut::ctrl("\n")),
+            Command::RenderRange => None,
+        }
+    }
+    pub fn from_shortcut(shortcut: &Shortcut) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|c| c.shortcut().as_ref() == Some(shortcut))
+    }
+    pub fn search_string(&self) -> String {
+        format!("{} {} {}", self.name(), self.description(), self.category())
+    }
+}
+
+/// A key combination, with `key` being the text slint reports for the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortcut {
+    pub key: String,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Shortcut {
+    pub fn new(key: &str, ctrl: bool, shift: bool, alt: bool) -> Self {
+        Self {
+            key: key.to_lowercase(),
+            ctrl,
+            shift,
+            alt,
+        }
+    }
+    fn ctrl(key: &str) -> Self {
+        Self::new(key, true, false, false)
+    }
+    fn ctrl_shift(key: &str) -> Self {
+        Self::new(key, true, true, false)
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        match self.key.as_str() {
+            "\n" => write!(f, "Enter"),
+            " " => write!(f, "Space"),
+            "\u{7f}" => write!(f, "Delete"),
+            key => write!(f, "{}", key.to_uppercase()),
+        }
+    }
+}
+
+/// What the command palette can search for.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PaletteEntry {
+    Node(NodeType),
+    Command(Command),
+    /// A template of the library, by name.
+    Template(String),
+    /// A node of the graph being viewed, by index.
+    GraphNode(usize),
+    /// A viewport bookmark of the graph, by name.
+    Bookmark(String),
+    /// A bookmark to create with the given name.
+    NewBookmark(String),
+}
+
+/// Creates a palette index that already knows about every command, nodes
+/// are added on top by each project.
+pub fn palette_index() -> SimSearch<PaletteEntry> {
+    let mut index = SimSearch::new_with(SearchOptions::new().threshold(0.65).levenshtein(false));
+    for command in Command::ALL {
+        index.insert(PaletteEntry::Command(*command), &command.search_string());
+    }
+    index
+}
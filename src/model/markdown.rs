@@ -0,0 +1,152 @@
+//! The small subset of Markdown rendered by note nodes: `# headings`,
+//! `**bold**` and `*italic*` or `_italic_` runs.
+
+/// A run of text sharing the same emphasis.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Line {
+    pub heading: bool,
+    pub spans: Vec<Span>,
+}
+
+pub fn parse(text: &str) -> Vec<Line> {
+    text.lines().map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Line {
+    let trimmed = line.trim_start();
+    let hashes = trimmed.chars().take_while(|c| *c == '#').count();
+    let (heading, line) = match trimmed[hashes..].strip_prefix(' ') {
+        Some(rest) if hashes > 0 => (true, rest.trim_start()),
+        _ => (false, line),
+    };
+
+    let mut spans = vec![];
+    let mut current = Span::default();
+    let mut prev = None;
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let marker = if rest.starts_with("**") {
+            Some("**")
+        } else if c == '*' || c == '_' {
+            Some(&rest[..1])
+        } else {
+            None
+        };
+        match marker {
+            Some(m) if toggles(m, &current, prev, &rest[m.len()..]) => {
+                let next = Span {
+                    text: String::new(),
+                    bold: current.bold ^ (m == "**"),
+                    italic: current.italic ^ (m != "**"),
+                };
+                let done = std::mem::replace(&mut current, next);
+                if !done.text.is_empty() {
+                    spans.push(done);
+                }
+                rest = &rest[m.len()..];
+            }
+            _ if c == '\\' && rest[1..].starts_with(['*', '_', '\\']) => {
+                current.text.push_str(&rest[1..2]);
+                rest = &rest[2..];
+            }
+            _ => {
+                current.text.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        prev = Some(c);
+    }
+    if !current.text.is_empty() {
+        spans.push(current);
+    }
+    Line { heading, spans }
+}
+
+/// Markers only open when they are closed later on the line, and `_` is
+/// ignored inside words so `snake_case` stays intact.
+fn toggles(marker: &str, current: &Span, prev: Option<char>, after: &str) -> bool {
+    let closing = if marker == "**" {
+        current.bold
+    } else {
+        current.italic
+    };
+    if marker == "_" {
+        let word_char = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+        if closing && word_char(after.chars().next()) || !closing && word_char(prev) {
+            return false;
+        }
+    }
+    closing || after.contains(marker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str, bold: bool, italic: bool) -> Span {
+        Span {
+            text: text.into(),
+            bold,
+            italic,
+        }
+    }
+
+    fn spans(text: &str) -> Vec<Span> {
+        parse_line(text).spans
+    }
+
+    #[test]
+    fn parses_emphasis() {
+        assert_eq!(
+            spans("**bold** and *italic* or _italic_"),
+            [
+                span("bold", true, false),
+                span(" and ", false, false),
+                span("italic", false, true),
+                span(" or ", false, false),
+                span("italic", false, true),
+            ]
+        );
+        assert_eq!(
+            parse_line("# A **title**"),
+            Line {
+                heading: true,
+                spans: vec![span("A ", false, false), span("title", true, false)],
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_underscores_inside_words() {
+        assert_eq!(
+            spans("call snake_case_name"),
+            [span("call snake_case_name", false, false)]
+        );
+        assert_eq!(spans("_snake_case_"), [span("snake_case", false, true)]);
+    }
+
+    #[test]
+    fn leaves_unclosed_markers_alone() {
+        assert_eq!(spans("**not bold"), [span("**not bold", false, false)]);
+        assert_eq!(
+            spans("**bold** but **not"),
+            [span("bold", true, false), span(" but **not", false, false)]
+        );
+    }
+
+    #[test]
+    fn escapes_markers() {
+        assert_eq!(
+            spans(r"\*not italic\* \\ \_"),
+            [span(r"*not italic* \ _", false, false)]
+        );
+        assert_eq!(spans(r"**a\*b**"), [span("a*b", true, false)]);
+    }
+}
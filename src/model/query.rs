@@ -0,0 +1,180 @@
+use super::{Command, LinkType, Node, NodeType, PaletteEntry, Template, Templates};
+use simsearch::SimSearch;
+use std::collections::HashMap;
+
+/// A palette search such as `cat:loaders in:IMAGE upscale`, filters are
+/// combined with the fuzzy matching of the remaining text.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PaletteQuery {
+    text: String,
+    category: Vec<String>,
+    input: Vec<String>,
+    output: Vec<String>,
+    pack: Vec<String>,
+    tag: Vec<String>,
+    scope: Scope,
+}
+
+/// What a query looks for, commands opening the palette for a single
+/// purpose narrow it down with one of the words below.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Commands, node types and templates.
+    #[default]
+    All,
+    /// `tpl:insert` and `tpl:new`, templates inserted into the graph or
+    /// opened in a new tab.
+    Templates { new_tab: bool },
+    /// `go:node`, the nodes of the graph being viewed.
+    GraphNodes,
+    /// `go:bookmark`, the viewport bookmarks of the graph.
+    Bookmarks,
+    /// `add:bookmark`, the text names a new bookmark.
+    AddBookmark,
+}
+
+impl PaletteQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut parsed = Self::default();
+        let mut text = vec![];
+        for word in query.split_whitespace() {
+            match word.split_once(':') {
+                Some(("cat", v)) if !v.is_empty() => parsed.category.push(v.to_lowercase()),
+                Some(("in", v)) if !v.is_empty() => parsed.input.push(v.to_lowercase()),
+                Some(("out", v)) if !v.is_empty() => parsed.output.push(v.to_lowercase()),
+                Some(("pack", v)) if !v.is_empty() => parsed.pack.push(v.to_lowercase()),
+                Some(("tag", v)) if !v.is_empty() => parsed.tag.push(v.to_lowercase()),
+                Some(("tpl", "insert")) => parsed.scope = Scope::Templates { new_tab: false },
+                Some(("tpl", "new")) => parsed.scope = Scope::Templates { new_tab: true },
+                Some(("go", "node")) => parsed.scope = Scope::GraphNodes,
+                Some(("go", "bookmark")) => parsed.scope = Scope::Bookmarks,
+                Some(("add", "bookmark")) => parsed.scope = Scope::AddBookmark,
+                _ => text.push(word),
+            }
+        }
+        parsed.text = text.join(" ");
+        parsed
+    }
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty() && !self.has_filters()
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+    /// Whether picking a template should open it in a new tab.
+    pub fn new_tab(&self) -> bool {
+        self.scope == Scope::Templates { new_tab: true }
+    }
+    fn has_filters(&self) -> bool {
+        !(self.category.is_empty()
+            && self.input.is_empty()
+            && self.output.is_empty()
+            && self.pack.is_empty()
+            && self.tag.is_empty()
+            && self.scope == Scope::All)
+    }
+    pub fn matches_node(&self, node: &Node) -> bool {
+        let category = node.category.to_lowercase();
+        let pack = node.pack.to_lowercase();
+        self.scope == Scope::All
+            && self.tag.is_empty()
+            && self.category.iter().all(|c| category.contains(c.as_str()))
+            && self.pack.iter().all(|p| pack.contains(p.as_str()))
+            && self
+                .input
+                .iter()
+                .all(|ty| node.inputs.iter().any(|(_, slot)| slot_matches(slot, ty)))
+            && self
+                .output
+                .iter()
+                .all(|ty| node.outputs.iter().any(|(_, slot)| slot_matches(slot, ty)))
+    }
+    pub fn matches_command(&self, command: Command) -> bool {
+        let category = command.category().to_lowercase();
+        self.scope == Scope::All
+            && self.tag.is_empty()
+            && self.input.is_empty()
+            && self.output.is_empty()
+            && self.pack.is_empty()
+            && self.category.iter().all(|c| category.contains(c.as_str()))
+    }
+    /// Ranks entries by fuzzy matching on the text, or lists everything that
+    /// passes the filters alphabetically when there is no text.
+    pub fn search(
+        &self,
+        index: &SimSearch<PaletteEntry>,
+        nodes: &HashMap<NodeType, Node>,
+    ) -> Vec<PaletteEntry> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let entries = if self.text.is_empty() {
+            let mut entries = Command::ALL
+                .iter()
+                .map(|c| (c.name().to_lowercase(), PaletteEntry::Command(*c)))
+                .chain(
+                    nodes
+                        .iter()
+                        .map(|(id, n)| (n.name.to_lowercase(), PaletteEntry::Node(id.clone()))),
+                )
+                .collect::<Vec<_>>();
+            entries.sort();
+            entries.into_iter().map(|(_, e)| e).collect()
+        } else {
+            index.search(&self.text)
+        };
+        entries
+            .into_iter()
+            .filter(|entry| match entry {
+                PaletteEntry::Node(id) => nodes.get(id).is_some_and(|n| self.matches_node(n)),
+                PaletteEntry::Command(c) => self.matches_command(*c),
+                _ => false,
+            })
+            .collect()
+    }
+    pub fn matches_template(&self, template: &Template) -> bool {
+        matches!(self.scope, Scope::All | Scope::Templates { .. })
+            && self.category.is_empty()
+            && self.input.is_empty()
+            && self.output.is_empty()
+            && self.pack.is_empty()
+            && self.tag.iter().all(|t| template.has_tag(t))
+    }
+    /// Same as [`PaletteQuery::search`] for the template library.
+    pub fn search_templates(&self, templates: &Templates) -> Vec<PaletteEntry> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let found = if self.text.is_empty() {
+            templates.all().iter().collect()
+        } else {
+            templates.search(&self.text)
+        };
+        found
+            .into_iter()
+            .filter(|t| self.matches_template(t))
+            .map(|t| PaletteEntry::Template(t.name.clone()))
+            .collect()
+    }
+    /// Keeps the entries whose name contains the text, in their order, for
+    /// listings too small to need an index.
+    pub fn filter_names(
+        &self,
+        entries: impl IntoIterator<Item = (String, PaletteEntry)>,
+    ) -> Vec<PaletteEntry> {
+        let text = self.text.to_lowercase();
+        entries
+            .into_iter()
+            .filter(|(name, _)| name.to_lowercase().contains(&text))
+            .map(|(_, entry)| entry)
+            .collect()
+    }
+}
+
+/// Wildcard slots match any type and any slot matches a wildcard filter.
+fn slot_matches(slot: &LinkType, ty: &str) -> bool {
+    slot.is_any() || ty == "*" || slot.0.eq_ignore_ascii_case(ty)
+}
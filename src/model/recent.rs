@@ -0,0 +1,50 @@
+use crate::utils::data_dir;
+use serde::{Deserialize, Serialize};
+use std::{fs::File, path::PathBuf};
+
+const MAX_RECENT_FILES: usize = 10;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RecentFiles {
+    paths: Vec<String>,
+}
+
+impl RecentFiles {
+    pub fn load() -> Self {
+        Self::file_path()
+            .and_then(|path| File::open(path).ok())
+            .and_then(|f| serde_json::from_reader(f).ok())
+            .unwrap_or_default()
+    }
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+    pub fn push(&mut self, path: &str) {
+        let path = std::fs::canonicalize(path)
+            .ok()
+            .and_then(|pb| pb.to_str().map(|s| s.to_owned()))
+            .unwrap_or_else(|| path.into());
+        self.paths.retain(|p| *p != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT_FILES);
+        self.save();
+    }
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.save();
+    }
+    fn save(&self) {
+        // history is a convenience, failing to persist it shouldn't get in the way
+        if let Some(path) = Self::file_path() {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            if let Ok(f) = File::create(&path) {
+                let _ = serde_json::to_writer(f, self);
+            }
+        }
+    }
+    fn file_path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("recent_files.json"))
+    }
+}
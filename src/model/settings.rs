@@ -0,0 +1,53 @@
+use crate::utils::data_dir;
+use serde::{Deserialize, Serialize};
+use std::{fs::File, path::PathBuf};
+
+const DEFAULT_GRID_SIZE: f32 = 20.;
+
+/// Editor preferences kept between sessions.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    snap_to_grid: bool,
+    grid_size: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            snap_to_grid: false,
+            grid_size: DEFAULT_GRID_SIZE,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        Self::file_path()
+            .and_then(|path| File::open(path).ok())
+            .and_then(|f| serde_json::from_reader(f).ok())
+            .unwrap_or_default()
+    }
+    /// Spacing moved nodes snap to, if snapping is on.
+    pub fn grid(&self) -> Option<f32> {
+        (self.snap_to_grid && self.grid_size > 0.).then_some(self.grid_size)
+    }
+    pub fn toggle_snap_to_grid(&mut self) {
+        self.snap_to_grid = !self.snap_to_grid;
+        self.save();
+    }
+    fn save(&self) {
+        // like the recent files, losing a preference isn't worth an error
+        if let Some(path) = Self::file_path() {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            if let Ok(f) = File::create(&path) {
+                let _ = serde_json::to_writer(f, self);
+            }
+        }
+    }
+    fn file_path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("settings.json"))
+    }
+}
@@ -24,11 +24,17 @@ impl Tabs {
     pub fn select_tab(&mut self, tab: usize) {
         self.selected_tab = Some(tab);
     }
+    pub fn projects_mut(&mut self) -> impl Iterator<Item = &mut Project> {
+        self.tabs.iter_mut()
+    }
     pub fn selected_tab(&self) -> Option<usize> {
         self.selected_tab
     }
     pub fn new_tab(&mut self) {
-        self.tabs.push(Project::new());
+        self.open_tab(Project::new());
+    }
+    pub fn open_tab(&mut self, project: Project) {
+        self.tabs.push(project);
         self.selected_tab = Some(self.tabs.len() - 1);
     }
     pub fn close_tab(&mut self, tab: usize) {
@@ -44,7 +50,12 @@ impl Tabs {
     pub fn tab_titles(&self) -> Vec<String> {
         self.tabs
             .iter()
-            .map(|t| t.file_path().unwrap_or("Untitled").into())
+            .map(|t| {
+                t.file_path()
+                    .or(t.source_path())
+                    .unwrap_or("Untitled")
+                    .into()
+            })
             .collect()
     }
 }
@@ -1,31 +1,205 @@
-use self::graph::{Graph, LinkType, NodeType};
-use simsearch::{SearchOptions, SimSearch};
-use std::collections::HashMap;
+use crate::model::{
+    command::{self, PaletteEntry},
+    query::{PaletteQuery, Scope},
+};
+use kira_core::{
+    align::{self, Alignment, Axis},
+    catalog::{self, Catalog},
+    file::{self, FileError, FileFormat},
+    graph::{self, Port, GROUP_COLORS},
+    layout, prompt, subgraph, DanglingLink, Graph, Group, Interpolation, Keyframe, Link, LinkType,
+    Node, NodeMode, NodeType, Segment, Subgraph, Timeline,
+};
+use serde_json::Value;
+use simsearch::SimSearch;
+use std::{borrow::Cow, collections::HashMap};
 
-pub mod graph;
+const MAX_UNDO_STEPS: usize = 100;
+const NODE_WIDTH: f32 = 100.;
+const NODE_BASE_HEIGHT: f32 = 60.;
+const SLOT_HEIGHT: f32 = 15.;
+/// Nodes are drawn slightly above their position.
+const NODE_TOP_MARGIN: f32 = 10.;
+/// Space kept between a new node and the ones around it.
+const PLACEMENT_GAP: f32 = 10.;
+const GROUP_PADDING: f32 = 10.;
+const GROUP_TITLE_HEIGHT: f32 = 30.;
+const MIN_GROUP_SIZE: f32 = 50.;
+const NOTE_WIDTH: f32 = 200.;
+const NOTE_BASE_HEIGHT: f32 = 50.;
+const NOTE_LINE_HEIGHT: f32 = 12.;
+/// Rough number of characters fitting on a line of a note.
+const NOTE_LINE_CHARS: usize = 40;
+const REROUTE_WIDTH: f32 = 40.;
+const REROUTE_HEIGHT: f32 = 25.;
+/// Room for the thumbnail of the images a node produced.
+const PREVIEW_HEIGHT: f32 = 80.;
+
+/// A range being rendered, frame by frame.
+#[derive(Debug)]
+pub struct Render {
+    ticket: String,
+    /// Ids the backend gave the prompts, empty until they are all queued.
+    ids: Vec<String>,
+    pub done: usize,
+    pub total: usize,
+    cancelled: bool,
+}
 
 #[derive(Debug)]
 pub struct Project {
-    available_node_index: SimSearch<NodeType>,
-    available_nodes: HashMap<NodeType, Node>,
-    graph: Graph,
+    palette_index: SimSearch<PaletteEntry>,
+    available_nodes: Catalog,
+    /// The top level graph, which also holds the subgraph definitions.
+    root: Graph,
+    /// Names of the nested subgraphs being viewed, outermost first.
+    breadcrumb: Vec<String>,
+    timeline: Timeline,
+    /// Tickets of the prompts sent from this project that haven't finished.
+    queued: Vec<String>,
+    render: Option<Render>,
+    undo_stack: Vec<(Graph, Timeline)>,
+    redo_stack: Vec<(Graph, Timeline)>,
     file_path: Option<String>,
+    source_path: Option<String>,
 }
 
 impl Project {
     pub fn new() -> Self {
-        Self {
-            available_nodes: HashMap::new(),
-            available_node_index: Self::empty_index(),
-            graph: Graph::new(),
+        let mut project = Self {
+            available_nodes: Catalog::new(),
+            palette_index: command::palette_index(),
+            root: Graph::new(),
+            breadcrumb: vec![],
+            timeline: Timeline::default(),
+            queued: vec![],
+            render: None,
+            undo_stack: vec![],
+            redo_stack: vec![],
             file_path: None,
+            source_path: None,
+        };
+        project.set_available_nodes(Catalog::new());
+        project
+    }
+    /// Kira files are saved back in place, anything else is imported and
+    /// only remembered as the source of the graph.
+    pub fn open(path: &str) -> Result<Self, FileError> {
+        let mut project = Self::new();
+        (project.root, project.timeline) = file::read_project(path)?;
+        project.build_index();
+        if FileFormat::from_path(path) == Some(FileFormat::Kira) {
+            project.file_path = Some(path.into());
+        } else {
+            project.source_path = Some(path.into());
         }
+        Ok(project)
     }
+    /// A project starting from a copy of `graph`, such as a template.
+    pub fn from_graph(graph: Graph) -> Self {
+        let mut project = Self::new();
+        project.root = graph;
+        project.build_index();
+        project
+    }
+    /// The graph being viewed, either the top level one or the innermost
+    /// subgraph of the breadcrumb.
     pub fn graph(&self) -> &Graph {
-        &self.graph
+        self.breadcrumb
+            .last()
+            .and_then(|name| self.root.subgraph(name))
+            .map_or(&self.root, |s| &s.graph)
     }
     pub fn graph_mut(&mut self) -> &mut Graph {
-        &mut self.graph
+        let name = self
+            .breadcrumb
+            .last()
+            .filter(|name| self.root.subgraph(name).is_some())
+            .cloned();
+        match name {
+            Some(name) => &mut self.root.subgraph_mut(&name).unwrap().graph,
+            None => &mut self.root,
+        }
+    }
+    /// The top level graph, as saved to disk.
+    pub fn root_graph(&self) -> &Graph {
+        &self.root
+    }
+    pub fn breadcrumb(&self) -> &[String] {
+        &self.breadcrumb
+    }
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+    /// Applies `edit` to the timeline as an undoable change, unless it
+    /// leaves the timeline as it was.
+    pub fn edit_timeline<T>(&mut self, edit: impl FnOnce(&mut Timeline) -> T) -> T {
+        let before = self.timeline.clone();
+        let result = edit(&mut self.timeline);
+        if self.timeline != before {
+            self.push_undo((self.root.clone(), before));
+        }
+        result
+    }
+    /// Views the inner graph of subgraph node `idx`.
+    pub fn open_subgraph(&mut self, idx: usize) {
+        let name = self
+            .graph()
+            .get_node(idx)
+            .and_then(|n| n.ty.subgraph_name())
+            .filter(|name| self.root.subgraph(name).is_some())
+            .map(str::to_owned);
+        if let Some(name) = name {
+            self.breadcrumb.push(name);
+        }
+    }
+    /// Goes back up the breadcrumb, keeping its first `depth` entries.
+    pub fn leave_subgraph(&mut self, depth: usize) {
+        self.breadcrumb.truncate(depth);
+    }
+    /// Records the current graph and timeline so the next change can be
+    /// undone.
+    pub fn checkpoint(&mut self) {
+        self.push_undo((self.root.clone(), self.timeline.clone()));
+    }
+    fn push_undo(&mut self, state: (Graph, Timeline)) {
+        self.undo_stack.push(state);
+        if self.undo_stack.len() > MAX_UNDO_STEPS {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+    pub fn undo(&mut self) {
+        if let Some(state) = self.undo_stack.pop() {
+            let current = self.restore(state);
+            self.redo_stack.push(current);
+        }
+    }
+    pub fn redo(&mut self) {
+        if let Some(state) = self.redo_stack.pop() {
+            let current = self.restore(state);
+            self.undo_stack.push(current);
+        }
+    }
+    /// Swaps in a previous state of the graph and timeline while keeping the
+    /// viewport where the user left it.
+    fn restore(&mut self, (mut graph, timeline): (Graph, Timeline)) -> (Graph, Timeline) {
+        graph.set_zoom(self.root.zoom());
+        graph.set_offset(self.root.offset());
+        graph.set_selection(self.root.selection().iter().copied());
+        let previous = (
+            std::mem::replace(&mut self.root, graph),
+            std::mem::replace(&mut self.timeline, timeline),
+        );
+        // the subgraph being viewed may not exist in that state
+        let depth = self
+            .breadcrumb
+            .iter()
+            .take_while(|name| self.root.subgraph(name).is_some())
+            .count();
+        self.breadcrumb.truncate(depth);
+        self.build_index();
+        previous
     }
     pub fn file_path(&self) -> Option<&str> {
         self.file_path.as_deref()
@@ -33,48 +207,737 @@ impl Project {
     pub fn set_file_path(&mut self, path: String) {
         self.file_path = Some(path);
     }
-    pub fn set_available_nodes(&mut self, nodes: HashMap<NodeType, Node>) {
+    pub fn source_path(&self) -> Option<&str> {
+        self.source_path.as_deref()
+    }
+    /// Sets the nodes offered by the backend, built-in nodes are always
+    /// available on top of them.
+    pub fn set_available_nodes(&mut self, mut nodes: Catalog) {
+        nodes.extend(catalog::builtin_nodes());
         self.available_nodes = nodes;
         self.build_index();
     }
-    pub fn search_available_nodes(&self, query: &str) -> Vec<(NodeType, Node)> {
-        let ids = self.available_node_index.search(query);
-        ids.into_iter()
-            .take(10)
-            .filter_map(|id| {
-                self.available_nodes
-                    .get_key_value(&id)
-                    .map(|(k, v)| (k.clone(), v.clone()))
-            })
-            .collect()
+    pub fn search_palette(&self, query: &PaletteQuery) -> Vec<PaletteEntry> {
+        match query.scope() {
+            Scope::GraphNodes => query.filter_names(
+                (0..self.graph().get_nodes().len())
+                    .map(|idx| (self.node_title(idx), PaletteEntry::GraphNode(idx))),
+            ),
+            Scope::Bookmarks => query.filter_names(
+                self.graph()
+                    .bookmarks()
+                    .iter()
+                    .map(|b| (b.name.clone(), PaletteEntry::Bookmark(b.name.clone()))),
+            ),
+            Scope::AddBookmark if !query.text().is_empty() => {
+                vec![PaletteEntry::NewBookmark(query.text().into())]
+            }
+            _ => query.search(&self.palette_index, &self.available_nodes),
+        }
+    }
+    /// How node `idx` is called in the editor, notes go by their first line.
+    pub fn node_title(&self, idx: usize) -> String {
+        let Some(node) = self.graph().get_node(idx) else {
+            return String::new();
+        };
+        match node.text.lines().next() {
+            Some(line) if node.ty.is_note() => line.trim_start_matches('#').trim().into(),
+            _ => self
+                .available_nodes
+                .get(&node.ty)
+                .map_or_else(|| node.ty.0.clone(), |n| n.name.clone()),
+        }
     }
     pub fn get_available_node(&self, id: &NodeType) -> Option<Node> {
         self.available_nodes.get(id).cloned()
     }
+    /// The node type of node `idx`, or a placeholder with the slots its
+    /// links use when the backend doesn't offer it.
+    pub fn node_info(&self, idx: usize) -> Option<Cow<'_, Node>> {
+        catalog::node_of(&self.available_nodes, self.graph(), idx)
+    }
+    /// Whether the type of node `idx` is one the backend doesn't offer.
+    pub fn is_missing_type(&self, idx: usize) -> bool {
+        self.graph()
+            .get_node(idx)
+            .is_some_and(|n| !self.available_nodes.contains_key(&n.ty))
+    }
+    /// Adds a node as close to `pos` as possible without covering others.
+    pub fn add_node(&mut self, ty: NodeType, pos: (f32, f32)) -> usize {
+        self.checkpoint();
+        let pos = self.free_position(&ty, pos);
+        self.graph_mut().add_node_at(ty, pos)
+    }
+    /// Adds a node and connects `link` to its first slot of the matching type,
+    /// as a single undoable change.
+    pub fn add_linked_node(&mut self, ty: NodeType, pos: (f32, f32), link: DanglingLink) {
+        let Some(node) = self.available_nodes.get(&ty).cloned() else {
+            return;
+        };
+        let idx = self.add_node(ty, pos);
+        let link = match link {
+            DanglingLink::FromOutput {
+                node: src_node,
+                slot: src_slot,
+                ty,
+            } => node
+                .inputs
+                .iter()
+                .position(|(_, t)| t.accepts(&ty))
+                .map(|dst_slot| Link {
+                    src_node,
+                    src_slot,
+                    dst_node: idx,
+                    dst_slot,
+                    ty: ty.resolve(&node.inputs[dst_slot].1),
+                }),
+            DanglingLink::ToInput {
+                node: dst_node,
+                slot: dst_slot,
+                ty,
+            } => node
+                .outputs
+                .iter()
+                .position(|(_, t)| t.accepts(&ty))
+                .map(|src_slot| Link {
+                    src_node: idx,
+                    src_slot,
+                    dst_node,
+                    dst_slot,
+                    ty: ty.resolve(&node.outputs[src_slot].1),
+                }),
+        };
+        if let Some(link) = link {
+            self.graph_mut().add_link(link);
+        }
+    }
+    /// Splits link `idx` with a reroute centred on `pos`, as a single
+    /// undoable change.
+    pub fn insert_reroute(&mut self, idx: usize, pos: (f32, f32)) {
+        let Some(link) = self.graph().get_links().get(idx).cloned() else {
+            return;
+        };
+        self.checkpoint();
+        self.graph_mut().remove_link(idx);
+        let reroute = self.graph_mut().add_node_at(
+            NodeType::reroute(),
+            (pos.0 - REROUTE_WIDTH / 2., pos.1 - SLOT_HEIGHT / 2.),
+        );
+        self.graph_mut().add_link(Link {
+            dst_node: reroute,
+            dst_slot: 0,
+            ..link.clone()
+        });
+        self.graph_mut().add_link(Link {
+            src_node: reroute,
+            src_slot: 0,
+            ..link
+        });
+    }
+    /// The type of a slot, reroutes take the type of the links attached to
+    /// them.
+    pub fn slot_type(&self, node_idx: usize, output: bool, slot: usize) -> Option<LinkType> {
+        let node = self.graph().get_node(node_idx)?;
+        if node.ty.is_reroute() {
+            return Some(
+                self.graph()
+                    .get_links()
+                    .iter()
+                    .filter(|l| l.src_node == node_idx || l.dst_node == node_idx)
+                    .map(|l| l.ty.clone())
+                    .find(|ty| !ty.is_any())
+                    .unwrap_or_else(LinkType::any),
+            );
+        }
+        let available = self.node_info(node_idx)?;
+        let slots = if output {
+            &available.outputs
+        } else {
+            &available.inputs
+        };
+        slots.get(slot).map(|(_, ty)| ty.clone())
+    }
+    /// The area covered by a node on the canvas, as `(x, y, width, height)`
+    /// in graph coordinates. The view draws nodes with this exact size.
+    pub fn node_rect(&self, idx: usize) -> Option<(f32, f32, f32, f32)> {
+        let node = self.graph().get_node(idx)?;
+        let slots = self.node_info(idx).map_or(0, |n| slot_count(&n));
+        let (width, mut height) = node_size(&node.ty, &node.text, slots);
+        if !node.outputs.is_empty() {
+            height += PREVIEW_HEIGHT;
+        }
+        Some((node.pos.0, node.pos.1 - NODE_TOP_MARGIN, width, height))
+    }
+    /// Moves `pos` down past any node a new node of type `ty` would overlap.
+    fn free_position(&self, ty: &NodeType, pos: (f32, f32)) -> (f32, f32) {
+        let slots = self.available_nodes.get(ty).map_or(0, slot_count);
+        let (width, height) = node_size(ty, "", slots);
+        let rects = (0..self.graph().get_nodes().len())
+            .filter_map(|idx| self.node_rect(idx))
+            .collect::<Vec<_>>();
+        let (x, mut y) = (pos.0, pos.1 - NODE_TOP_MARGIN);
+        // every step moves below one more node, so this always terminates
+        for _ in 0..=rects.len() {
+            let overlap = rects.iter().find(|(nx, ny, nw, nh)| {
+                *nx < x + width + PLACEMENT_GAP
+                    && nx + nw + PLACEMENT_GAP > x
+                    && *ny < y + height + PLACEMENT_GAP
+                    && ny + nh + PLACEMENT_GAP > y
+            });
+            match overlap {
+                Some((_, ny, _, nh)) => y = ny + nh + PLACEMENT_GAP,
+                None => break,
+            }
+        }
+        (x, y + NODE_TOP_MARGIN)
+    }
+    /// Selects the nodes overlapping `rect`, given as `(x, y, width, height)`.
+    pub fn select_in_rect(&mut self, rect: (f32, f32, f32, f32), extend: bool) {
+        let (x, y, w, h) = rect;
+        let hits = (0..self.graph().get_nodes().len())
+            .filter(|idx| {
+                self.node_rect(*idx).is_some_and(|(nx, ny, nw, nh)| {
+                    nx < x + w && nx + nw > x && ny < y + h && ny + nh > y
+                })
+            })
+            .collect::<Vec<_>>();
+        if extend {
+            let selection = self.graph().selection().clone();
+            self.graph_mut()
+                .set_selection(selection.into_iter().chain(hits));
+        } else {
+            self.graph_mut().set_selection(hits);
+        }
+    }
+    /// Switches the selected nodes to `mode`, or back to active when they
+    /// all are in that mode already.
+    pub fn toggle_mode(&mut self, mode: NodeMode) {
+        let selected = self.graph().selection().clone();
+        if selected.is_empty() {
+            return;
+        }
+        let all = selected
+            .iter()
+            .all(|idx| self.graph().get_node(*idx).is_some_and(|n| n.mode == mode));
+        self.checkpoint();
+        for idx in selected {
+            self.graph_mut()
+                .set_node_mode(idx, if all { NodeMode::Active } else { mode });
+        }
+    }
+    /// Keys `widget` of the selected nodes to `value` at `time`.
+    pub fn keyframe_selection(
+        &mut self,
+        widget: String,
+        time: f32,
+        value: Value,
+        interpolation: Interpolation,
+    ) {
+        let selected = self.graph().selection().clone();
+        if selected.is_empty() {
+            return;
+        }
+        self.checkpoint();
+        for idx in selected {
+            let keyframe = Keyframe {
+                time,
+                value: value.clone(),
+                interpolation,
+            };
+            self.graph_mut().set_keyframe(idx, widget.clone(), keyframe);
+        }
+    }
+    /// Removes the keyframes of the selected nodes on the frame at `time`.
+    pub fn remove_selection_keyframes(&mut self, time: f32) {
+        let selected = self.graph().selection().clone();
+        if selected.is_empty() {
+            return;
+        }
+        let tolerance = 0.5 / self.timeline.fps();
+        self.checkpoint();
+        for idx in selected {
+            self.graph_mut().remove_keyframes(idx, time, tolerance);
+        }
+    }
+    /// Times of the keyframes anywhere in the project, subgraphs included.
+    pub fn keyframe_times(&self) -> Vec<f32> {
+        let mut times = self
+            .flattened()
+            .get_nodes()
+            .iter()
+            .flat_map(|n| n.keyframes.values().flatten().map(|k| k.time))
+            .collect::<Vec<_>>();
+        times.sort_by(f32::total_cmp);
+        times.dedup();
+        times
+    }
+    /// Moves node `idx` to `pos`, lining it up with the other nodes or the
+    /// `grid` when it lands close to them.
+    pub fn move_node(&mut self, idx: usize, pos: (f32, f32), grid: Option<f32>) {
+        let Some((old_x, old_y, w, h)) = self.node_rect(idx) else {
+            return;
+        };
+        let others = (0..self.graph().get_nodes().len())
+            .filter(|other| *other != idx)
+            .filter_map(|other| self.node_rect(other))
+            .collect::<Vec<_>>();
+        let (x, y) = align::snap((pos.0, pos.1 - NODE_TOP_MARGIN, w, h), &others, grid);
+        // a click without a drag releases the node where it was
+        if (x, y) == (old_x, old_y) {
+            return;
+        }
+        self.checkpoint();
+        self.graph_mut()
+            .set_node_position(idx, x, y + NODE_TOP_MARGIN);
+    }
+    /// Lines the selected nodes up, as a single undoable change.
+    pub fn align_selection(&mut self, alignment: Alignment) {
+        let nodes = self.selected_rects();
+        if nodes.len() >= 2 {
+            self.set_corners(align::align(&nodes, alignment));
+        }
+    }
+    /// Spaces the selected nodes evenly, as a single undoable change.
+    pub fn distribute_selection(&mut self, axis: Axis) {
+        let nodes = self.selected_rects();
+        if nodes.len() >= 3 {
+            self.set_corners(align::distribute(&nodes, axis));
+        }
+    }
+    fn selected_rects(&self) -> Vec<(usize, (f32, f32, f32, f32))> {
+        self.graph()
+            .selection()
+            .iter()
+            .filter_map(|idx| self.node_rect(*idx).map(|rect| (*idx, rect)))
+            .collect()
+    }
+    /// Moves nodes so their rectangles start at the given corners.
+    fn set_corners(&mut self, corners: Vec<(usize, (f32, f32))>) {
+        self.checkpoint();
+        for (idx, (x, y)) in corners {
+            self.graph_mut()
+                .set_node_position(idx, x, y + NODE_TOP_MARGIN);
+        }
+    }
+    /// Lays out the selected nodes, or the whole graph when nothing is
+    /// selected, as a single undoable change.
+    pub fn auto_layout(&mut self) {
+        let ids = if self.graph().selection().is_empty() {
+            (0..self.graph().get_nodes().len()).collect::<Vec<_>>()
+        } else {
+            self.graph().selection().iter().copied().collect()
+        };
+        let nodes = ids
+            .into_iter()
+            .filter_map(|idx| self.node_rect(idx).map(|rect| (idx, rect)))
+            .collect::<Vec<_>>();
+        if nodes.len() < 2 {
+            return;
+        }
+        let links = self
+            .graph()
+            .get_links()
+            .iter()
+            .map(|l| (l.src_node, l.dst_node))
+            .collect::<Vec<_>>();
+        let positions = layout::layered(&nodes, &links);
+
+        self.checkpoint();
+        for (idx, (x, y)) in positions {
+            self.graph_mut()
+                .set_node_position(idx, x, y + NODE_TOP_MARGIN);
+        }
+    }
+    /// Frames the selected nodes in a new group.
+    pub fn group_selection(&mut self) {
+        let rects = self
+            .graph()
+            .selection()
+            .iter()
+            .filter_map(|idx| self.node_rect(*idx))
+            .collect::<Vec<_>>();
+        let Some((x, y, w, h)) = graph::bounding_box(rects) else {
+            return;
+        };
+        self.checkpoint();
+        self.graph_mut().add_group(Group {
+            title: "Group".into(),
+            color: GROUP_COLORS[0].into(),
+            rect: (
+                x - GROUP_PADDING,
+                y - GROUP_PADDING - GROUP_TITLE_HEIGHT,
+                w + 2. * GROUP_PADDING,
+                h + 2. * GROUP_PADDING + GROUP_TITLE_HEIGHT,
+            ),
+        });
+    }
+    /// Moves a group's top-left corner to `pos`, taking along the nodes and
+    /// groups lying entirely inside it, as a single undoable change.
+    pub fn move_group(&mut self, idx: usize, pos: (f32, f32)) {
+        let Some(rect) = self.graph().get_group(idx).map(|g| g.rect) else {
+            return;
+        };
+        let (dx, dy) = (pos.0 - rect.0, pos.1 - rect.1);
+        if (dx, dy) == (0., 0.) {
+            return;
+        }
+        let nodes = (0..self.graph().get_nodes().len())
+            .filter(|n| self.node_rect(*n).is_some_and(|r| graph::contains(rect, r)))
+            .collect::<Vec<_>>();
+        let groups = self
+            .graph()
+            .get_groups()
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| graph::contains(rect, g.rect))
+            .map(|(i, g)| (i, g.rect))
+            .collect::<Vec<_>>();
+
+        self.checkpoint();
+        for n in nodes {
+            let (x, y) = self.graph().get_nodes()[n].pos;
+            self.graph_mut().set_node_position(n, x + dx, y + dy);
+        }
+        for (i, (x, y, w, h)) in groups {
+            self.graph_mut().set_group_rect(i, (x + dx, y + dy, w, h));
+        }
+    }
+    pub fn resize_group(&mut self, idx: usize, size: (f32, f32)) {
+        let Some((x, y, _, _)) = self.graph().get_group(idx).map(|g| g.rect) else {
+            return;
+        };
+        self.checkpoint();
+        self.graph_mut().set_group_rect(
+            idx,
+            (x, y, size.0.max(MIN_GROUP_SIZE), size.1.max(MIN_GROUP_SIZE)),
+        );
+    }
+    /// Fits the selected nodes into view, if any.
+    pub fn zoom_to_selection(&mut self, viewport: (f32, f32)) {
+        let rects = self
+            .graph()
+            .selection()
+            .iter()
+            .filter_map(|idx| self.node_rect(*idx))
+            .collect::<Vec<_>>();
+        if let Some(bounds) = graph::bounding_box(rects) {
+            self.graph_mut().fit_view(bounds, viewport);
+        }
+    }
+    /// Selects node `idx` and scrolls it into the middle of the view.
+    pub fn center_node(&mut self, idx: usize, viewport: (f32, f32)) {
+        if let Some((x, y, w, h)) = self.node_rect(idx) {
+            let graph = self.graph_mut();
+            graph.select_node(idx, false);
+            graph.center_on((x + w / 2., y + h / 2.), viewport);
+        }
+    }
+    /// Rectangle around every node of the graph being viewed.
+    pub fn bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        graph::bounding_box(
+            (0..self.graph().get_nodes().len()).filter_map(|idx| self.node_rect(idx)),
+        )
+    }
+    pub fn zoom_to_fit(&mut self, viewport: (f32, f32)) {
+        if let Some(bounds) = self.bounds() {
+            self.graph_mut().fit_view(bounds, viewport);
+        }
+    }
+    /// Replaces the selected nodes with a single node standing for a new
+    /// subgraph, whose slots are the links crossing the selection boundary.
+    pub fn collapse_selection(&mut self) {
+        let selected = self.graph().selection().clone();
+        if selected.is_empty() {
+            return;
+        }
+        let Some((x, y, _, _)) =
+            graph::bounding_box(selected.iter().filter_map(|idx| self.node_rect(*idx)))
+        else {
+            return;
+        };
+        let mut name = "Subgraph".to_owned();
+        let mut n = 1;
+        while self.root.subgraph(&name).is_some() {
+            n += 1;
+            name = format!("Subgraph {}", n);
+        }
+
+        let graph = self.graph();
+        let port = |node: usize, output: bool, slot: usize, inner: usize| Port {
+            name: self
+                .available_nodes
+                .get(&graph.get_nodes()[node].ty)
+                .and_then(|n| if output { &n.outputs } else { &n.inputs }.get(slot))
+                .map(|(name, _)| name.clone())
+                .unwrap_or_default(),
+            ty: self
+                .slot_type(node, output, slot)
+                .unwrap_or_else(LinkType::any),
+            node: inner,
+            slot,
+        };
+        let (inner, incoming, outgoing) = subgraph::extract(graph, &selected);
+        let inputs = incoming
+            .iter()
+            .map(|(link, inner)| port(link.dst_node, false, link.dst_slot, *inner))
+            .collect();
+        let outputs = outgoing
+            .iter()
+            .map(|((node, slot), (inner, _))| port(*node, true, *slot, *inner))
+            .collect();
+        let subgraph = Subgraph {
+            graph: inner,
+            inputs,
+            outputs,
+        };
+
+        self.checkpoint();
+        self.root.add_subgraph(name.clone(), subgraph);
+        let graph = self.graph_mut();
+        graph.remove_nodes(&selected);
+        let remap = |idx: usize| idx - selected.range(..idx).count();
+        let node = graph.add_node_at(NodeType::subgraph(&name), (x, y + NODE_TOP_MARGIN));
+        for (slot, (link, _)) in incoming.into_iter().enumerate() {
+            graph.add_link(Link {
+                src_node: remap(link.src_node),
+                dst_node: node,
+                dst_slot: slot,
+                ..link
+            });
+        }
+        for (slot, (_, (_, links))) in outgoing.into_iter().enumerate() {
+            for link in links {
+                graph.add_link(Link {
+                    src_node: node,
+                    src_slot: slot,
+                    dst_node: remap(link.dst_node),
+                    ..link
+                });
+            }
+        }
+        graph.set_selection([node]);
+        self.build_index();
+    }
+    /// Copies the selected nodes, or the whole graph being viewed when
+    /// nothing is selected, along with the subgraph definitions they use.
+    /// The copy is moved so its nodes start at the origin.
+    pub fn snapshot(&self) -> Graph {
+        let graph = self.graph();
+        let mut snapshot = if graph.selection().is_empty() {
+            let mut snapshot = graph.clone();
+            snapshot.take_subgraphs();
+            snapshot
+        } else {
+            subgraph::extract(graph, graph.selection()).0
+        };
+        snapshot.clear_selection();
+        for (name, definition) in subgraph::used(&snapshot, self.root.subgraphs()) {
+            snapshot.add_subgraph(name, definition);
+        }
+        if let Some((x, y, _, _)) = graph::bounding_box(
+            snapshot
+                .get_nodes()
+                .iter()
+                .map(|n| (n.pos.0, n.pos.1, 0., 0.)),
+        ) {
+            snapshot.translate((-x, -y));
+        }
+        snapshot.set_offset((PLACEMENT_GAP, PLACEMENT_GAP + NODE_TOP_MARGIN));
+        snapshot
+    }
+    /// Adds the nodes of `graph` with their top left corner at `pos` and
+    /// selects them. Subgraph definitions are added unless the project
+    /// already has the same one, those differing from a project definition
+    /// of the same name are renamed.
+    pub fn insert_graph(&mut self, mut graph: Graph, pos: (f32, f32)) {
+        self.checkpoint();
+        let mut definitions = graph.take_subgraphs();
+        let renames = subgraph::renames(self.root.subgraphs(), &definitions);
+        subgraph::retype(&mut graph, &renames);
+        for definition in definitions.values_mut() {
+            subgraph::retype(&mut definition.graph, &renames);
+        }
+        for (name, definition) in definitions {
+            let name = renames.get(&name).cloned().unwrap_or(name);
+            if self.root.subgraph(&name).is_none() {
+                self.root.add_subgraph(name, definition);
+            }
+        }
+        let origin =
+            graph::bounding_box(graph.get_nodes().iter().map(|n| (n.pos.0, n.pos.1, 0., 0.)))
+                .map_or((0., 0.), |(x, y, _, _)| (x, y));
+        let added = self
+            .graph_mut()
+            .append(graph, (pos.0 - origin.0, pos.1 - origin.1));
+        self.graph_mut().set_selection(added);
+        self.build_index();
+    }
+    /// The top level graph with every subgraph node replaced by its
+    /// contents, as understood by the backend.
+    pub fn flattened(&self) -> Graph {
+        subgraph::flatten(&self.root)
+    }
+    /// What keeps the graph from running at all, like subgraphs containing
+    /// themselves, see [`prompt::problems`]. Node types are left to the
+    /// backend to check.
+    pub fn problems(&self) -> Vec<String> {
+        prompt::problems(&self.root, None)
+    }
+    /// Builds the API prompt the backend executes with keyframed widgets
+    /// set to their value at `time`, see [`prompt::build`].
+    pub fn to_prompt_at(&self, time: f32) -> Value {
+        prompt::build(&self.root, &self.available_nodes, time)
+    }
+    /// One prompt per frame from the first keyframe to the last, with the
+    /// time of each frame.
+    pub fn render_prompts(&self) -> Vec<(f32, Value)> {
+        prompt::render_range(&self.root, &self.available_nodes, self.timeline.fps())
+    }
+    pub fn prompt_queued(&mut self, ticket: String) {
+        self.queued.push(ticket);
+    }
+    /// Forgets prompt `ticket`, returns false if it wasn't sent from this
+    /// project.
+    pub fn prompt_failed(&mut self, ticket: &str) -> bool {
+        self.take_queued(ticket)
+    }
+    fn take_queued(&mut self, ticket: &str) -> bool {
+        let Some(pos) = self.queued.iter().position(|q| q == ticket) else {
+            return false;
+        };
+        self.queued.remove(pos);
+        if self.render.as_ref().is_some_and(|r| r.ticket == ticket) {
+            self.render = None;
+        }
+        true
+    }
+    /// Starts tracking the render of a range of `total` frames.
+    pub fn render_started(&mut self, ticket: String, total: usize) {
+        self.prompt_queued(ticket.clone());
+        self.render = Some(Render {
+            ticket,
+            ids: vec![],
+            done: 0,
+            total,
+            cancelled: false,
+        });
+    }
+    /// Remembers the ids the backend gave the prompts of render `ticket`.
+    /// Returns them if the render was cancelled while they were queued.
+    pub fn render_queued(&mut self, ticket: &str, ids: Vec<String>) -> Option<Vec<String>> {
+        let render = self.render.as_mut().filter(|r| r.ticket == ticket)?;
+        render.ids = ids;
+        render.cancelled.then(|| render.ids.clone())
+    }
+    pub fn render_progressed(&mut self, ticket: &str, done: usize) {
+        if let Some(render) = self.render.as_mut().filter(|r| r.ticket == ticket) {
+            render.done = done;
+        }
+    }
+    /// Marks the render as cancelled and returns the ids of its prompts to
+    /// take off the queue, which may not be known yet.
+    pub fn cancel_render(&mut self) -> Option<Vec<String>> {
+        let render = self.render.as_mut()?;
+        render.cancelled = true;
+        Some(render.ids.clone())
+    }
+    /// Whether `ticket` is a render that was cancelled, its failure is then
+    /// expected.
+    pub fn is_cancelled(&self, ticket: &str) -> bool {
+        self.render
+            .as_ref()
+            .is_some_and(|r| r.ticket == ticket && r.cancelled)
+    }
+    pub fn render(&self) -> Option<&Render> {
+        self.render.as_ref()
+    }
+    /// Adds the images of a rendered range, one per frame, to the timeline
+    /// as a segment starting at `start`. Returns false if the range wasn't
+    /// rendered from this project.
+    pub fn finish_render(&mut self, ticket: &str, start: f32, frames: Vec<String>) -> bool {
+        // cancelled too late to stop any of its prompts
+        let cancelled = self.is_cancelled(ticket);
+        if !self.take_queued(ticket) {
+            return false;
+        }
+        if cancelled {
+            return true;
+        }
+        let track = self
+            .timeline
+            .tracks()
+            .iter()
+            .position(|t| !t.locked)
+            .unwrap_or_default();
+        let duration = frames.len() as f32 / self.timeline.fps();
+        self.edit_timeline(|timeline| {
+            timeline.add_segment(Segment {
+                duration,
+                ..Segment::new(track, start, "Render".into(), frames)
+            })
+        });
+        true
+    }
+    /// Hands the images prompt `ticket` produced, by prompt node id, to the
+    /// nodes of the top level graph. Returns false if the prompt wasn't
+    /// sent from this project.
+    pub fn finish_prompt(&mut self, ticket: &str, outputs: &HashMap<String, Vec<String>>) -> bool {
+        if !self.take_queued(ticket) {
+            return false;
+        }
+        for (idx, flat_idx) in subgraph::flat_indices(&self.root).into_iter().enumerate() {
+            if let Some(paths) = flat_idx.and_then(|i| outputs.get(&i.to_string())) {
+                self.root.set_node_outputs(idx, paths.clone());
+            }
+        }
+        true
+    }
+    /// Writes the flattened graph as a ComfyUI workflow, which is lossy so
+    /// the project keeps its own file path.
+    pub fn export_workflow(&self, path: &str) -> Result<(), FileError> {
+        let graph = self.flattened();
+        let sizes = graph
+            .get_nodes()
+            .iter()
+            .enumerate()
+            .map(|(idx, n)| {
+                let slots = catalog::node_of(&self.available_nodes, &graph, idx)
+                    .map_or(0, |n| slot_count(&n));
+                node_size(&n.ty, &n.text, slots)
+            })
+            .collect::<Vec<_>>();
+        file::save_workflow(path, &graph, &self.available_nodes, &sizes)
+    }
+    /// Rebuilds the palette, subgraph definitions are offered as nodes too.
     fn build_index(&mut self) {
-        self.available_node_index = Self::empty_index();
+        catalog::set_subgraphs(&mut self.available_nodes, &self.root);
+        self.palette_index = command::palette_index();
 
         for (k, v) in self.available_nodes.iter() {
-            self.available_node_index
-                .insert(k.clone(), &v.search_string());
+            self.palette_index
+                .insert(PaletteEntry::Node(k.clone()), &v.search_string());
         }
     }
-    fn empty_index() -> SimSearch<NodeType> {
-        SimSearch::new_with(SearchOptions::new().threshold(0.65).levenshtein(false))
-    }
 }
 
-#[derive(Debug, Clone)]
-pub struct Node {
-    pub inputs: Vec<(String, LinkType)>,
-    pub outputs: Vec<(String, LinkType)>,
-    pub name: String,
-    pub description: String,
-    pub category: String,
+/// Size of a node with `slots` rows of slots, notes grow with their text.
+fn node_size(ty: &NodeType, text: &str, slots: usize) -> (f32, f32) {
+    if ty.is_reroute() {
+        return (REROUTE_WIDTH, REROUTE_HEIGHT);
+    }
+    if ty.is_note() {
+        let lines = text
+            .lines()
+            .map(|l| l.chars().count().div_ceil(NOTE_LINE_CHARS).max(1))
+            .sum::<usize>();
+        return (
+            NOTE_WIDTH,
+            NOTE_BASE_HEIGHT + NOTE_LINE_HEIGHT * lines as f32,
+        );
+    }
+    (NODE_WIDTH, NODE_BASE_HEIGHT + SLOT_HEIGHT * slots as f32)
 }
 
-impl Node {
-    pub fn search_string(&self) -> String {
-        format!("{} {} {}", self.name, self.description, self.category)
-    }
+fn slot_count(node: &Node) -> usize {
+    node.inputs.len().max(node.outputs.len())
 }
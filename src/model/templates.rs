@@ -0,0 +1,111 @@
+use super::Graph;
+use crate::utils::data_dir;
+use serde::{Deserialize, Serialize};
+use simsearch::{SearchOptions, SimSearch};
+use std::{fs::File, io, path::PathBuf};
+
+/// A reusable piece of graph, with its nodes moved so they start at the
+/// origin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub graph: Graph,
+}
+
+impl Template {
+    pub fn search_string(&self) -> String {
+        format!("{} {} {}", self.name, self.description, self.tags.join(" "))
+    }
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.to_lowercase().contains(tag))
+    }
+}
+
+/// The user's template library, one JSON file per template in the data
+/// directory.
+#[derive(Debug)]
+pub struct Templates {
+    templates: Vec<Template>,
+    index: SimSearch<String>,
+}
+
+impl Templates {
+    pub fn load() -> Self {
+        let mut templates = Self::dir()
+            .and_then(|dir| std::fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            // a broken template shouldn't keep the others from loading
+            .filter_map(|entry| File::open(entry.path()).ok())
+            .filter_map(|f| serde_json::from_reader::<_, Template>(f).ok())
+            .collect::<Vec<_>>();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut templates = Self {
+            templates,
+            index: empty_index(),
+        };
+        templates.build_index();
+        templates
+    }
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.iter().find(|t| t.name == name)
+    }
+    pub fn all(&self) -> &[Template] {
+        &self.templates
+    }
+    /// Fuzzy matches the templates against `text`, best matches first.
+    pub fn search(&self, text: &str) -> Vec<&Template> {
+        self.index
+            .search(text)
+            .iter()
+            .filter_map(|name| self.get(name))
+            .collect()
+    }
+    /// Stores `template`, replacing any template of the same name.
+    pub fn save(&mut self, template: Template) -> io::Result<()> {
+        let dir = Self::dir().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no data directory for templates")
+        })?;
+        std::fs::create_dir_all(&dir)?;
+        let f = File::create(dir.join(format!("{}.json", file_stem(&template.name))))?;
+        serde_json::to_writer(f, &template)?;
+        self.templates.retain(|t| t.name != template.name);
+        self.templates.push(template);
+        self.templates.sort_by(|a, b| a.name.cmp(&b.name));
+        self.build_index();
+        Ok(())
+    }
+    fn build_index(&mut self) {
+        self.index = empty_index();
+        for template in &self.templates {
+            self.index
+                .insert(template.name.clone(), &template.search_string());
+        }
+    }
+    fn dir() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("templates"))
+    }
+}
+
+fn empty_index() -> SimSearch<String> {
+    SimSearch::new_with(SearchOptions::new().threshold(0.65).levenshtein(false))
+}
+
+/// Keeps template names usable as file names on every platform.
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -1,4 +1,8 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    env,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
 
 pub struct Aro<T>(Arc<RwLock<T>>);
 impl<T> Aro<T> {
@@ -39,3 +43,17 @@ impl<T> From<Arw<T>> for Aro<T> {
         Self(value.0)
     }
 }
+
+/// Per-user directory where kira keeps its own state between sessions.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    base.map(|base| base.join("kira"))
+}